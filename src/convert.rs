@@ -1,10 +1,15 @@
-use crate::{JCompound, JValue, JValueMut};
+use crate::{convert_object_list, JCompound, JValue, JValueMut, Schema, SchemaError};
 use java_string::{JavaStr, JavaString};
+use log::warn;
+use std::borrow::{Borrow, Cow};
+use std::cell::{Cell, OnceCell};
 use std::cmp::Ordering;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct DataVersion {
     version: u32,
     step: u32,
@@ -24,6 +29,20 @@ impl DataVersion {
     pub fn get_step(&self) -> u32 {
         self.step
     }
+
+    /// The version before this one, with step 0. The previous version of version 0 is version 0.
+    pub fn previous(&self) -> Self {
+        Self::new(self.version.saturating_sub(1), 0)
+    }
+
+    /// The version after this one, with step 0. The next version of `u32::MAX` is `u32::MAX`.
+    pub fn next(&self) -> Self {
+        Self::new(self.version.saturating_add(1), 0)
+    }
+
+    pub fn with_step(&self, step: u32) -> Self {
+        Self::new(self.version, step)
+    }
 }
 
 impl From<u32> for DataVersion {
@@ -32,6 +51,72 @@ impl From<u32> for DataVersion {
     }
 }
 
+/// A half-open range of data versions, including `from` and excluding `to`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct DataVersionRange {
+    from: DataVersion,
+    to: DataVersion,
+}
+
+impl DataVersionRange {
+    pub fn new(from: impl Into<DataVersion>, to: impl Into<DataVersion>) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    #[inline]
+    pub fn get_from(&self) -> DataVersion {
+        self.from
+    }
+
+    #[inline]
+    pub fn get_to(&self) -> DataVersion {
+        self.to
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.from >= self.to
+    }
+
+    #[inline]
+    pub fn contains(&self, version: impl Into<DataVersion>) -> bool {
+        let version = version.into();
+        self.from <= version && version < self.to
+    }
+
+    /// Whether any version is in both ranges. Adjacent ranges don't overlap.
+    pub fn overlaps(&self, other: &DataVersionRange) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    /// The versions in both ranges, or `None` if there are none.
+    pub fn intersection(&self, other: &DataVersionRange) -> Option<DataVersionRange> {
+        let intersection = DataVersionRange::new(self.from.max(other.from), self.to.min(other.to));
+        (!intersection.is_empty()).then_some(intersection)
+    }
+
+    /// The versions in either range, or `None` if that isn't a single range because there is a
+    /// gap between them. Adjacent ranges can be joined, and an empty range joins with anything.
+    pub fn union(&self, other: &DataVersionRange) -> Option<DataVersionRange> {
+        if self.is_empty() {
+            return Some(*other);
+        }
+        if other.is_empty() {
+            return Some(*self);
+        }
+        if self.from > other.to || other.from > self.to {
+            return None;
+        }
+        Some(DataVersionRange::new(
+            self.from.min(other.from),
+            self.to.max(other.to),
+        ))
+    }
+}
+
 pub trait MapDataConverterFunc {
     fn convert(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion);
 }
@@ -57,6 +142,54 @@ where
     DataConverterFuncImpl(func)
 }
 
+/// The key of the compound in which [`run_once_at`] records the converters which have run.
+pub const RUN_ONCE_MARKER_KEY: &str = "world_transmuter_engine:run_once";
+
+/// Wraps `converter_func` so that it runs at most once per compound, however many times the data
+/// is converted across `version`, which should be the version the result is registered at.
+/// [`MapDataType::add_run_once_converter`] registers it at the right version.
+///
+/// The state lives in the data itself: after the function runs, a byte is set in the compound at
+/// [`RUN_ONCE_MARKER_KEY`] under the key `"{version}/{name}"` (or `"{version}.{step}/{name}"`),
+/// and the function is skipped while it's set. `name` distinguishes converters at the same
+/// version. Remove the markers with [`remove_run_once_markers`] once the data won't be converted
+/// again, e.g. before saving it.
+pub fn run_once_at<'a>(
+    version: impl Into<DataVersion>,
+    name: &str,
+    converter_func: impl MapDataConverterFunc + 'a,
+) -> impl MapDataConverterFunc + 'a {
+    let version = version.into();
+    let marker = if version.step == 0 {
+        JavaString::from(format!("{}/{name}", version.version))
+    } else {
+        JavaString::from(format!("{}.{}/{name}", version.version, version.step))
+    };
+    map_data_converter_func(move |data, from_version, to_version| {
+        if let Some(JValue::Compound(markers)) = data.get(RUN_ONCE_MARKER_KEY) {
+            if markers.contains_key(&marker[..]) {
+                return;
+            }
+        }
+        converter_func.convert(data, from_version, to_version);
+        match data.get_mut(RUN_ONCE_MARKER_KEY) {
+            Some(JValue::Compound(markers)) => {
+                markers.insert(marker.clone(), JValue::Byte(1));
+            }
+            _ => {
+                let mut markers = JCompound::new();
+                markers.insert(marker.clone(), JValue::Byte(1));
+                data.insert(RUN_ONCE_MARKER_KEY, markers);
+            }
+        }
+    })
+}
+
+/// Removes the markers added by [`run_once_at`]. Returns whether there were any.
+pub fn remove_run_once_markers(data: &mut JCompound) -> bool {
+    data.remove(RUN_ONCE_MARKER_KEY).is_some()
+}
+
 impl<T: MapDataConverterFunc + ?Sized> MapDataConverterFunc for &T {
     fn convert(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion) {
         T::convert(self, data, from_version, to_version)
@@ -69,9 +202,17 @@ impl<T: MapDataConverterFunc + ?Sized> MapDataConverterFunc for Box<T> {
     }
 }
 
+impl<T: MapDataConverterFunc + ?Sized> MapDataConverterFunc for Rc<T> {
+    fn convert(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion) {
+        T::convert(self, data, from_version, to_version)
+    }
+}
+
+#[derive(Clone)]
 pub struct MapDataConverter<F: MapDataConverterFunc> {
     to_version: DataVersion,
     conversion_func: F,
+    label: Option<Rc<str>>,
 }
 
 impl<F: MapDataConverterFunc> MapDataConverter<F> {
@@ -79,14 +220,26 @@ impl<F: MapDataConverterFunc> MapDataConverter<F> {
         Self {
             to_version: to_version.into(),
             conversion_func,
+            label: None,
         }
     }
 
+    /// Attaches a human-readable label for debugging. It has no effect on conversion.
+    pub fn with_label(mut self, label: impl Into<Rc<str>>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
     #[inline]
     pub fn get_to_version(&self) -> DataVersion {
         self.to_version
     }
 
+    #[inline]
+    pub fn get_label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
     pub fn convert(
         &self,
         data: &mut JCompound,
@@ -135,9 +288,17 @@ impl<T: ValueDataConverterFunc + ?Sized> ValueDataConverterFunc for Box<T> {
     }
 }
 
+impl<T: ValueDataConverterFunc + ?Sized> ValueDataConverterFunc for Rc<T> {
+    fn convert(&self, data: &mut JValueMut, from_version: DataVersion, to_version: DataVersion) {
+        T::convert(self, data, from_version, to_version)
+    }
+}
+
+#[derive(Clone)]
 pub struct ValueDataConverter<F: ValueDataConverterFunc> {
     to_version: DataVersion,
     conversion_func: F,
+    label: Option<Rc<str>>,
 }
 
 impl<F: ValueDataConverterFunc> ValueDataConverter<F> {
@@ -145,14 +306,26 @@ impl<F: ValueDataConverterFunc> ValueDataConverter<F> {
         Self {
             to_version: to_version.into(),
             conversion_func,
+            label: None,
         }
     }
 
+    /// Attaches a human-readable label for debugging. It has no effect on conversion.
+    pub fn with_label(mut self, label: impl Into<Rc<str>>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
     #[inline]
     pub fn get_to_version(&self) -> DataVersion {
         self.to_version
     }
 
+    #[inline]
+    pub fn get_label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
     pub fn convert(
         &self,
         data: &mut JValueMut,
@@ -196,9 +369,17 @@ impl<T: DynamicDataConverterFunc + ?Sized> DynamicDataConverterFunc for Box<T> {
     }
 }
 
+impl<T: DynamicDataConverterFunc + ?Sized> DynamicDataConverterFunc for Rc<T> {
+    fn convert(&self, data: &mut JValue, from_version: DataVersion, to_version: DataVersion) {
+        T::convert(self, data, from_version, to_version)
+    }
+}
+
+#[derive(Clone)]
 pub struct DynamicDataConverter<F: DynamicDataConverterFunc> {
     to_version: DataVersion,
     conversion_func: F,
+    label: Option<Rc<str>>,
 }
 
 impl<F: DynamicDataConverterFunc> DynamicDataConverter<F> {
@@ -206,14 +387,26 @@ impl<F: DynamicDataConverterFunc> DynamicDataConverter<F> {
         Self {
             to_version: to_version.into(),
             conversion_func,
+            label: None,
         }
     }
 
+    /// Attaches a human-readable label for debugging. It has no effect on conversion.
+    pub fn with_label(mut self, label: impl Into<Rc<str>>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
     #[inline]
     pub fn get_to_version(&self) -> DataVersion {
         self.to_version
     }
 
+    #[inline]
+    pub fn get_label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
     pub fn convert(
         &self,
         data: &mut JValue,
@@ -229,11 +422,18 @@ macro_rules! impl_traits {
     ($converter:ident, $converter_func_trait:ident) => {
         impl<F: $converter_func_trait> core::fmt::Debug for $converter<F> {
             fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> std::fmt::Result {
-                write!(
-                    f,
-                    concat!(stringify!($converter), "{{{:?}}}"),
-                    self.to_version
-                )
+                match &self.label {
+                    Some(label) => write!(
+                        f,
+                        concat!(stringify!($converter), "{{{:?}, {:?}}}"),
+                        self.to_version, label
+                    ),
+                    None => write!(
+                        f,
+                        concat!(stringify!($converter), "{{{:?}}}"),
+                        self.to_version
+                    ),
+                }
             }
         }
 
@@ -263,17 +463,79 @@ impl_traits!(MapDataConverter, MapDataConverterFunc);
 impl_traits!(ValueDataConverter, ValueDataConverterFunc);
 impl_traits!(DynamicDataConverter, DynamicDataConverterFunc);
 
+#[derive(Debug)]
 pub struct ConversionError {
     pub message: String,
 }
 
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
 pub type Result<T> = core::result::Result<T, ConversionError>;
 
 pub trait AbstractMapDataType {
     fn convert(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion);
+
+    fn convert_self_versioned(&self, data: &mut JCompound, to_version: DataVersion) {
+        convert_self_versioned(self, data, JavaStr::from_str("DataVersion"), to_version)
+    }
+
+    /// Converts data which stores its own version as an int under `version_key`, and updates that
+    /// version afterwards. Data without a valid version is left untouched, as is data being
+    /// converted to a version with a non-zero step or which doesn't fit in an int, since that
+    /// version couldn't be stored.
+    fn convert_self_versioned_with_key(
+        &self,
+        data: &mut JCompound,
+        version_key: impl AsRef<JavaStr>,
+        to_version: DataVersion,
+    ) where
+        Self: Sized,
+    {
+        convert_self_versioned(self, data, version_key.as_ref(), to_version)
+    }
+}
+
+fn convert_self_versioned(
+    data_type: &(impl AbstractMapDataType + ?Sized),
+    data: &mut JCompound,
+    version_key: &JavaStr,
+    to_version: DataVersion,
+) {
+    let Some(&JValue::Int(from_version)) = data.get(version_key) else {
+        return;
+    };
+    let Ok(from_version) = u32::try_from(from_version) else {
+        return;
+    };
+    let from_version = DataVersion::from(from_version);
+    if from_version >= to_version {
+        return;
+    }
+    let stored_version = match i32::try_from(to_version.get_version()) {
+        Ok(stored_version) if to_version.get_step() == 0 => stored_version,
+        _ => {
+            warn!("Cannot store version {to_version:?} under {version_key}, not converting");
+            return;
+        }
+    };
+
+    data_type.convert(data, from_version, to_version);
+    data.insert(version_key, JValue::Int(stored_version));
+}
+
+impl<T: AbstractMapDataType + ?Sized> AbstractMapDataType for &T {
+    fn convert(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion) {
+        T::convert(self, data, from_version, to_version)
+    }
 }
 
-impl<T: AbstractMapDataType> AbstractMapDataType for &T {
+impl<T: AbstractMapDataType + ?Sized> AbstractMapDataType for Box<T> {
     fn convert(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion) {
         T::convert(self, data, from_version, to_version)
     }
@@ -290,7 +552,13 @@ pub trait AbstractValueDataType {
     fn convert(&self, data: &mut JValueMut, from_version: DataVersion, to_version: DataVersion);
 }
 
-impl<T: AbstractValueDataType> AbstractValueDataType for &T {
+impl<T: AbstractValueDataType + ?Sized> AbstractValueDataType for &T {
+    fn convert(&self, data: &mut JValueMut, from_version: DataVersion, to_version: DataVersion) {
+        T::convert(self, data, from_version, to_version)
+    }
+}
+
+impl<T: AbstractValueDataType + ?Sized> AbstractValueDataType for Box<T> {
     fn convert(&self, data: &mut JValueMut, from_version: DataVersion, to_version: DataVersion) {
         T::convert(self, data, from_version, to_version)
     }
@@ -307,7 +575,13 @@ pub trait AbstractDynamicDataType {
     fn convert(&self, data: &mut JValue, from_version: DataVersion, to_version: DataVersion);
 }
 
-impl<T: AbstractDynamicDataType> AbstractDynamicDataType for &T {
+impl<T: AbstractDynamicDataType + ?Sized> AbstractDynamicDataType for &T {
+    fn convert(&self, data: &mut JValue, from_version: DataVersion, to_version: DataVersion) {
+        T::convert(self, data, from_version, to_version)
+    }
+}
+
+impl<T: AbstractDynamicDataType + ?Sized> AbstractDynamicDataType for Box<T> {
     fn convert(&self, data: &mut JValue, from_version: DataVersion, to_version: DataVersion) {
         T::convert(self, data, from_version, to_version)
     }
@@ -321,26 +595,141 @@ impl<T: AbstractDynamicDataType> AbstractDynamicDataType for std::sync::RwLock<T
 }
 
 macro_rules! structure_converters {
-    ($ty:ident, $field_name:ident, $data_converter:ident, $converter_func:ident) => {
+    ($ty:ident, $field_name:ident, $data_converter:ident, $converter_func:ident, $data:ty) => {
         impl<'a> $ty<'a> {
             pub fn add_structure_converter(
                 &mut self,
                 version: impl Into<DataVersion>,
                 func: impl $converter_func + 'a,
             ) {
-                let dyn_box: Box<dyn $converter_func> = Box::new(func);
-                let converter = $data_converter::new(version, dyn_box);
-                let index = self.$field_name.binary_search(&converter);
-                let index = match index {
-                    Ok(i) => i,
-                    Err(i) => i,
-                };
+                let dyn_rc: Rc<dyn $converter_func> = Rc::new(func);
+                let converter = $data_converter::new(version, dyn_rc);
+                // insert after any converters with the same version, so they run in registration order
+                let index = self.$field_name.partition_point(|c| c <= &converter);
+                self.$field_name.insert(index, converter);
+            }
+
+            /// Like `add_structure_converter`, but labels the converter with `name`, which is
+            /// shown when debugging but doesn't affect conversion.
+            pub fn add_named_structure_converter(
+                &mut self,
+                version: impl Into<DataVersion>,
+                name: impl Into<Rc<str>>,
+                func: impl $converter_func + 'a,
+            ) {
+                let dyn_rc: Rc<dyn $converter_func> = Rc::new(func);
+                let converter = $data_converter::new(version, dyn_rc).with_label(name);
+                let index = self.$field_name.partition_point(|c| c <= &converter);
                 self.$field_name.insert(index, converter);
             }
+
+            /// Adds many converters at once, sorting only once at the end. The resulting order is
+            /// the same as calling `add_structure_converter` for each element in turn.
+            pub fn extend_from<V, F>(&mut self, converters: impl IntoIterator<Item = (V, F)>)
+            where
+                V: Into<DataVersion>,
+                F: $converter_func + 'a,
+            {
+                self.$field_name
+                    .extend(converters.into_iter().map(|(version, func)| {
+                        let dyn_rc: Rc<dyn $converter_func> = Rc::new(func);
+                        $data_converter::new(version, dyn_rc)
+                    }));
+                // stable sort, so converters with equal versions keep their insertion order
+                self.$field_name.sort();
+            }
+
+            /// Returns, for each converter in order, the range from the version of the previous
+            /// converter with a lower version (or version 0) to the version of the converter, with
+            /// the label of the converter if it has one. This is the range of versions the
+            /// converter upgrades data from, assuming the data is already at or above the version
+            /// of the previous converter.
+            pub fn converter_windows(&self) -> Vec<(DataVersionRange, Option<Rc<str>>)> {
+                let mut windows = Vec::with_capacity(self.$field_name.len());
+                let mut window_start = DataVersion::new(0, 0);
+                let mut previous = DataVersion::new(0, 0);
+                for converter in &self.$field_name {
+                    if converter.get_to_version() != previous {
+                        window_start = previous;
+                        previous = converter.get_to_version();
+                    }
+                    windows.push((
+                        DataVersionRange::new(window_start, previous),
+                        converter.label.clone(),
+                    ));
+                }
+                windows
+            }
+
+            /// Checks that every converter targets a version within `range`, returning the
+            /// versions of those that don't.
+            pub fn validate_versions(
+                &self,
+                range: DataVersionRange,
+            ) -> core::result::Result<(), Vec<DataVersion>> {
+                let start = self
+                    .$field_name
+                    .partition_point(|converter| converter.get_to_version() < range.get_from());
+                let end = self
+                    .$field_name
+                    .partition_point(|converter| converter.get_to_version() < range.get_to())
+                    .max(start);
+                if start == 0 && end == self.$field_name.len() {
+                    return Ok(());
+                }
+                Err(self.$field_name[..start]
+                    .iter()
+                    .chain(&self.$field_name[end..])
+                    .map(|converter| converter.get_to_version())
+                    .collect())
+            }
+
+            /// Runs only the converters registered at exactly `version`, passing the version of
+            /// the preceding converter (or version 0) as the from version. Returns whether any
+            /// converter was found.
+            pub fn run_single_converter(
+                &self,
+                version: impl Into<DataVersion>,
+                data: &mut $data,
+            ) -> bool {
+                let version = version.into();
+                let start = self
+                    .$field_name
+                    .partition_point(|converter| converter.get_to_version() < version);
+                let from_version = match start {
+                    0 => DataVersion::new(0, 0),
+                    _ => self.$field_name[start - 1].get_to_version(),
+                };
+                let mut found = false;
+                for converter in &self.$field_name[start..] {
+                    if converter.get_to_version() != version {
+                        break;
+                    }
+                    converter.convert(data, from_version, version);
+                    found = true;
+                }
+                found
+            }
+        }
+    };
+}
+
+macro_rules! default_with_empty_name {
+    ($ty:ident) => {
+        impl Default for $ty<'_> {
+            fn default() -> Self {
+                Self::new("")
+            }
         }
     };
 }
 
+/// The versions in a version list, e.g. for the `Debug` impls of the data types, which can't print
+/// the boxed functions themselves.
+fn debug_versions<T>(versions: &BTreeMap<DataVersion, Vec<T>>) -> Vec<DataVersion> {
+    versions.keys().copied().collect()
+}
+
 macro_rules! version_list {
     ($ty:ident, $method_name:ident, $field_name:ident, $element_type:ty) => {
         impl<'a> $ty<'a> {
@@ -348,25 +737,176 @@ macro_rules! version_list {
                 self.$field_name
                     .entry(version.into())
                     .or_default()
-                    .push(Box::new(value));
+                    .push(Rc::new(value));
+            }
+        }
+    };
+}
+
+macro_rules! version_range_list {
+    (@ranges $self:ident) => {
+        None
+    };
+    (@ranges $self:ident $ranges_field:ident) => {
+        Some(&mut $self.$ranges_field)
+    };
+    (
+        $ty:ident,
+        $method_name:ident,
+        $field_name:ident,
+        $element_trait:ident
+        $(, $ranges_field:ident)?
+    ) => {
+        impl<'a> $ty<'a> {
+            /// Registers `value` so that it applies when converting to any version within `range`.
+            ///
+            /// These are selected from the nearest registered version at or below the target
+            /// version, so `value` is added at `range.from` (which, as with a normal registration,
+            /// shadows anything registered at earlier versions) and at every version already
+            /// registered inside the range. If nothing is registered at the end of the range, what
+            /// was in effect just before it (without `value`) is re-registered there so that
+            /// `value` stops applying. Versions first registered inside the range after this call
+            /// will not include `value`.
+            pub fn $method_name(
+                &mut self,
+                range: DataVersionRange,
+                value: impl $element_trait + 'a,
+            ) {
+                let value: Rc<dyn $element_trait + 'a> = Rc::new(value);
+                add_for_range(
+                    &mut self.$field_name,
+                    version_range_list!(@ranges self $($ranges_field)?),
+                    range,
+                    value,
+                );
+            }
+        }
+    };
+}
+
+macro_rules! walker_since {
+    ($ty:ident, $field_name:ident, $walker_trait:ident, $data:ty) => {
+        impl<'a> $ty<'a> {
+            /// Like `add_structure_walker`, but the walker only runs when the conversion crosses
+            /// `version`, i.e. it is skipped when converting from `version` or later. Walkers added
+            /// with `add_structure_walker` run for every conversion to `version` or later.
+            pub fn add_structure_walker_since(
+                &mut self,
+                version: impl Into<DataVersion>,
+                walker: impl $walker_trait + 'a,
+            ) {
+                struct WalkerSince<W> {
+                    since: DataVersion,
+                    walker: W,
+                }
+                impl<W: $walker_trait> $walker_trait for WalkerSince<W> {
+                    fn walk(
+                        &self,
+                        data: &mut $data,
+                        from_version: DataVersion,
+                        to_version: DataVersion,
+                    ) {
+                        if from_version < self.since {
+                            self.walker.walk(data, from_version, to_version);
+                        }
+                    }
+                }
+
+                let version = version.into();
+                self.$field_name
+                    .entry(version)
+                    .or_default()
+                    .push(Rc::new(WalkerSince {
+                        since: version,
+                        walker,
+                    }));
             }
         }
     };
 }
 
-type DynMapDataConverterFunc<'a> = Box<dyn MapDataConverterFunc + 'a>;
+/// What `add_for_range` has registered, so that mistakes with it can be found later.
+struct RangeRegistrations<T: ?Sized> {
+    /// Every value registered for a range, with its range.
+    values: Vec<(DataVersionRange, Rc<T>)>,
+    /// The versions at the end of a range which `add_for_range` had to register, with the version
+    /// whose values were copied there.
+    ends: BTreeMap<DataVersion, Option<DataVersion>>,
+}
+
+impl<T: ?Sized> Default for RangeRegistrations<T> {
+    fn default() -> Self {
+        Self {
+            values: Vec::new(),
+            ends: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: ?Sized> Clone for RangeRegistrations<T> {
+    fn clone(&self) -> Self {
+        Self {
+            values: self.values.clone(),
+            ends: self.ends.clone(),
+        }
+    }
+}
+
+fn add_for_range<T: ?Sized>(
+    map: &mut BTreeMap<DataVersion, Vec<Rc<T>>>,
+    ranges: Option<&mut RangeRegistrations<T>>,
+    range: DataVersionRange,
+    value: Rc<T>,
+) {
+    if range.is_empty() {
+        return;
+    }
+
+    let mut copied_from = None;
+    if !map.contains_key(&range.to) {
+        let previous = map.range(..range.to).next_back();
+        copied_from = Some(previous.map(|(&version, _)| version));
+        let previous = previous
+            .map(|(_, values)| values.clone())
+            .unwrap_or_default();
+        map.insert(range.to, previous);
+    }
+
+    if let Some(ranges) = ranges {
+        ranges.values.push((range, value.clone()));
+        if let Some(copied_from) = copied_from {
+            ranges.ends.insert(range.to, copied_from);
+        }
+    }
+
+    map.entry(range.from).or_default();
+    for (_, values) in map.range_mut(range.from..range.to) {
+        values.push(value.clone());
+    }
+}
+
+type DynMapDataConverterFunc<'a> = Rc<dyn MapDataConverterFunc + 'a>;
 
+#[derive(Clone)]
 pub struct MapDataType<'a> {
     pub name: String,
     structure_converters: Vec<MapDataConverter<DynMapDataConverterFunc<'a>>>,
-    structure_walkers: BTreeMap<DataVersion, Vec<Box<dyn MapDataWalker + 'a>>>,
-    structure_hooks: BTreeMap<DataVersion, Vec<Box<dyn MapDataHook + 'a>>>,
+    structure_walkers: BTreeMap<DataVersion, Vec<Rc<dyn MapDataWalker + 'a>>>,
+    walker_ranges: RangeRegistrations<dyn MapDataWalker + 'a>,
+    try_structure_walkers: BTreeMap<DataVersion, Vec<Rc<dyn TryMapDataWalker + 'a>>>,
+    structure_hooks: BTreeMap<DataVersion, Vec<Rc<dyn MapDataHook + 'a>>>,
+    per_converter_hooks: Vec<Rc<dyn MapDataHook + 'a>>,
+    observer: Option<Rc<dyn ConversionObserver + 'a>>,
+    downgrade_converters: Vec<MapDataConverter<DynMapDataConverterFunc<'a>>>,
+    terminal_converters: Vec<MapDataConverter<DynMapDataConverterFunc<'a>>>,
+    schemas: BTreeMap<DataVersion, Rc<Schema>>,
 }
 structure_converters!(
     MapDataType,
     structure_converters,
     MapDataConverter,
-    MapDataConverterFunc
+    MapDataConverterFunc,
+    JCompound
 );
 version_list!(
     MapDataType,
@@ -374,88 +914,769 @@ version_list!(
     structure_walkers,
     impl MapDataWalker + 'a
 );
+version_range_list!(
+    MapDataType,
+    add_walker_for_range,
+    structure_walkers,
+    MapDataWalker,
+    walker_ranges
+);
+walker_since!(MapDataType, structure_walkers, MapDataWalker, JCompound);
+version_list!(
+    MapDataType,
+    add_try_structure_walker,
+    try_structure_walkers,
+    impl TryMapDataWalker + 'a
+);
 version_list!(
     MapDataType,
     add_structure_hook,
     structure_hooks,
     impl MapDataHook + 'a
 );
+default_with_empty_name!(MapDataType);
+impl Debug for MapDataType<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MapDataType")
+            .field("name", &self.name)
+            .field("converters", &self.structure_converters)
+            .field("walkers", &debug_versions(&self.structure_walkers))
+            .field("try_walkers", &debug_versions(&self.try_structure_walkers))
+            .field("hooks", &debug_versions(&self.structure_hooks))
+            .field("per_converter_hooks", &self.per_converter_hooks.len())
+            .field("downgrade_converters", &self.downgrade_converters)
+            .field("terminal_converters", &self.terminal_converters)
+            .field("schemas", &self.schemas.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
 impl<'a> MapDataType<'a> {
     pub fn new(name: impl Into<String>) -> Self {
         Self {
             name: name.into(),
             structure_converters: Vec::new(),
             structure_walkers: BTreeMap::new(),
+            walker_ranges: RangeRegistrations::default(),
+            try_structure_walkers: BTreeMap::new(),
             structure_hooks: BTreeMap::new(),
+            per_converter_hooks: Vec::new(),
+            observer: None,
+            downgrade_converters: Vec::new(),
+            terminal_converters: Vec::new(),
+            schemas: BTreeMap::new(),
         }
     }
-}
-
-impl<'a> AbstractMapDataType for MapDataType<'a> {
-    fn convert(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion) {
-        for converter in &self.structure_converters {
-            if converter.get_to_version() <= from_version {
-                continue;
-            }
-            if converter.get_to_version() > to_version {
-                break;
-            }
 
-            let hooks = self
-                .structure_hooks
-                .range(..=converter.get_to_version())
-                .next_back();
-            if let Some((_, hooks)) = hooks {
-                for hook in hooks {
-                    hook.pre_hook(data, from_version, to_version);
+    /// Returns a type which converts the data at `path` with this type, instead of the data it is
+    /// given. `path` is a sequence of keys separated by `/`, each but the last naming a nested
+    /// compound. If the data at the end of the path is a compound it is converted, and if it is a
+    /// list of compounds each element is converted. Data which the path doesn't resolve in is left
+    /// untouched. Scoping applies to all the converters, hooks and walkers of this type.
+    pub fn scoped(self, path: &str) -> impl AbstractMapDataType + 'a {
+        struct Scoped<'a> {
+            typ: MapDataType<'a>,
+            path: Vec<JavaString>,
+        }
+        impl AbstractMapDataType for Scoped<'_> {
+            fn convert(
+                &self,
+                data: &mut JCompound,
+                from_version: DataVersion,
+                to_version: DataVersion,
+            ) {
+                let Some((last, parents)) = self.path.split_last() else {
+                    self.typ.convert(data, from_version, to_version);
+                    return;
+                };
+                let mut data = data;
+                for key in parents {
+                    let Some(JValue::Compound(child)) = data.get_mut(&key[..]) else {
+                        return;
+                    };
+                    data = child;
                 }
-            }
-
-            converter.convert(data, from_version, to_version);
-
-            // possibly new data format, update hooks
-            let hooks = self.structure_hooks.range(..=to_version).next_back();
-            if let Some((_, hooks)) = hooks {
-                for hook in hooks.iter().rev() {
-                    hook.post_hook(data, from_version, to_version);
+                match data.get_mut(&last[..]) {
+                    Some(JValue::Compound(map)) => self.typ.convert(map, from_version, to_version),
+                    Some(JValue::List(valence_nbt::List::Compound(list))) => {
+                        for map in list {
+                            self.typ.convert(map, from_version, to_version);
+                        }
+                    }
+                    _ => {}
                 }
             }
         }
 
-        let hooks = self.structure_hooks.range(..=to_version).next_back();
-        if let Some((_, hooks)) = hooks {
-            for hook in hooks {
-                hook.pre_hook(data, from_version, to_version);
-            }
+        Scoped {
+            typ: self,
+            path: path
+                .split('/')
+                .filter(|key| !key.is_empty())
+                .map(JavaString::from)
+                .collect(),
         }
+    }
 
-        let walkers = self.structure_walkers.range(..=to_version).next_back();
-        if let Some((_, walkers)) = walkers {
-            for walker in walkers {
-                walker.walk(data, from_version, to_version);
-            }
-        }
+    /// Adds a converter which undoes the changes made to the data format at `version`, converting
+    /// data from `version` to the version before it. Downgrade converters are only run by
+    /// [`convert_down`](Self::convert_down), never by the forward conversion.
+    pub fn add_downgrade_converter(
+        &mut self,
+        version: impl Into<DataVersion>,
+        converter_func: impl MapDataConverterFunc + 'a,
+    ) {
+        let dyn_rc: Rc<dyn MapDataConverterFunc> = Rc::new(converter_func);
+        let converter = MapDataConverter::new(version, dyn_rc);
+        // insert after any converters with the same version, so they run in reverse registration
+        // order
+        let index = self
+            .downgrade_converters
+            .partition_point(|c| c <= &converter);
+        self.downgrade_converters.insert(index, converter);
+    }
 
-        if let Some((_, hooks)) = hooks {
-            for hook in hooks.iter().rev() {
-                hook.post_hook(data, from_version, to_version);
-            }
+    /// Converts data from a newer version to an older one, running the downgrade converters
+    /// after `to_version` and up to `from_version` in reverse version order. Each converter is
+    /// passed the original `from_version` and `to_version`.
+    ///
+    /// Downgrades are best-effort and inherently lossy: anything the older format can't represent
+    /// is lost. Hooks and walkers are not run, so downgrade converters must handle any nested data
+    /// themselves. Does nothing unless `from_version > to_version`.
+    pub fn convert_down(
+        &self,
+        data: &mut JCompound,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) {
+        if from_version <= to_version {
+            return;
+        }
+        let start = self
+            .downgrade_converters
+            .partition_point(|converter| converter.get_to_version() <= to_version);
+        let end = self
+            .downgrade_converters
+            .partition_point(|converter| converter.get_to_version() <= from_version);
+        for converter in self.downgrade_converters[start..end].iter().rev() {
+            converter.convert(data, from_version, to_version);
         }
     }
-}
 
-type DynValueDataConverterFunc<'a> = Box<dyn ValueDataConverterFunc + 'a>;
+    /// Adds a converter which runs once at the very end of any conversion which crosses into
+    /// `min_version`, i.e. from before it to it or later, for normalization that doesn't belong to
+    /// a single version. Terminal converters run in order of `min_version`, then registration,
+    /// after all other converters, the walkers and the post hooks around the walkers, so they see
+    /// the fully converted data. They aren't surrounded by hooks, and don't run if a walker added
+    /// with `add_try_structure_walker` failed.
+    pub fn add_terminal_converter(
+        &mut self,
+        min_version: impl Into<DataVersion>,
+        converter_func: impl MapDataConverterFunc + 'a,
+    ) {
+        let dyn_rc: Rc<dyn MapDataConverterFunc> = Rc::new(converter_func);
+        let converter = MapDataConverter::new(min_version, dyn_rc);
+        let index = self
+            .terminal_converters
+            .partition_point(|c| c <= &converter);
+        self.terminal_converters.insert(index, converter);
+    }
+
+    /// Adds a converter which runs at most once per compound, wrapping it with [`run_once_at`] at
+    /// the version it's registered at, so the two can't disagree. The converter is labelled with
+    /// `name`.
+    pub fn add_run_once_converter(
+        &mut self,
+        version: impl Into<DataVersion>,
+        name: &str,
+        converter_func: impl MapDataConverterFunc + 'a,
+    ) {
+        let version = version.into();
+        self.add_named_structure_converter(
+            version,
+            name,
+            run_once_at(version, name, converter_func),
+        );
+    }
+
+    /// Sets the schema that data at `version` and later is expected to conform to, until the next
+    /// version with a schema.
+    pub fn add_schema(&mut self, version: impl Into<DataVersion>, schema: Schema) {
+        self.schemas.insert(version.into(), Rc::new(schema));
+    }
+
+    /// Checks `data` against the schema for `version`, if there is one. Validation is never run
+    /// automatically, but can be run from a hook or before and after converting.
+    pub fn validate(
+        &self,
+        data: &JCompound,
+        version: impl Into<DataVersion>,
+    ) -> core::result::Result<(), Vec<SchemaError>> {
+        match self.schemas.range(..=version.into()).next_back() {
+            Some((_, schema)) => schema.validate(data),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds a hook which runs around every converter, regardless of its version. These hooks are
+    /// the outermost: their pre hooks run before the pre hooks of the version-selected structure
+    /// hooks, and their post hooks run after the post hooks of the structure hooks, in reverse
+    /// order of registration. They don't run around the walkers.
+    pub fn add_per_converter_hook(&mut self, hook: impl MapDataHook + 'a) {
+        self.per_converter_hooks.push(Rc::new(hook));
+    }
+
+    /// Installs an observer which is notified of each converter, walker and hook run by
+    /// [`convert`](AbstractMapDataType::convert).
+    pub fn with_observer(mut self, observer: impl ConversionObserver + 'a) -> Self {
+        self.observer = Some(Rc::new(observer));
+        self
+    }
+
+    /// Checks how the versions of the registrations line up, for catching mistakes in large
+    /// registries. Converters and walkers are opaque, so which data shapes they expect can't be
+    /// checked. The problems found, one per line, are:
+    ///
+    /// - walkers, try walkers and hooks registered after the version of the last converter,
+    ///   usually meaning that the converter for that version is missing or registered at the
+    ///   wrong version;
+    /// - versions without any walkers, which [`add_walker_for_range`](Self::add_walker_for_range)
+    ///   leaves at the end of a range when there were no walkers before it, and which stop all
+    ///   walkers registered at earlier versions from applying;
+    /// - walkers which stop applying at the end of a range added with `add_walker_for_range`,
+    ///   because they were registered after it and so weren't copied to the end of the range.
+    pub fn check_monotonic(&self) -> core::result::Result<(), String> {
+        let mut problems = Vec::new();
+
+        if let Some(last_converter) = self.structure_converters.last() {
+            let last_version = last_converter.get_to_version();
+            let mut walker_versions = debug_versions(&self.structure_walkers);
+            // the ends of ranges aren't registered by the user
+            walker_versions.retain(|version| !self.walker_ranges.ends.contains_key(version));
+            let registrations = [
+                ("walkers", walker_versions),
+                ("try walkers", debug_versions(&self.try_structure_walkers)),
+                ("hooks", debug_versions(&self.structure_hooks)),
+            ];
+            for (kind, versions) in registrations {
+                for version in versions {
+                    if version > last_version {
+                        problems.push(format!(
+                            "{kind} registered at {version:?}, after the last converter at \
+                             {last_version:?}"
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (version, walkers) in &self.structure_walkers {
+            if walkers.is_empty() {
+                problems.push(format!(
+                    "no walkers apply from {version:?}, the end of a walker range"
+                ));
+            }
+        }
+
+        for (&end, &copied_from) in &self.walker_ranges.ends {
+            let Some(at_end) = self.structure_walkers.get(&end) else {
+                continue;
+            };
+            let mut versions: Vec<_> = self
+                .structure_walkers
+                .range(..end)
+                .next_back()
+                .map(|(&version, _)| version)
+                .into_iter()
+                .chain(copied_from)
+                .collect();
+            versions.dedup();
+            for version in versions {
+                let mut walkers = self.structure_walkers.get(&version).into_iter().flatten();
+                let shadowed = walkers.any(|walker| {
+                    !at_end.iter().any(|other| Rc::ptr_eq(walker, other))
+                        && !self
+                            .walker_ranges
+                            .values
+                            .iter()
+                            .any(|(range, value)| range.to <= end && Rc::ptr_eq(walker, value))
+                });
+                if shadowed {
+                    problems.push(format!(
+                        "walkers registered at {version:?} stop applying at {end:?}, the end of a \
+                         walker range added before them"
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("\n"))
+        }
+    }
+
+    /// Returns a hash of the versions of the converters, walkers, hooks and schemas, and how many
+    /// there are at each version, ignoring what they actually do. The hash is stable across runs
+    /// and platforms, so it can be stored to detect the registered converters changing.
+    pub fn fingerprint(&self) -> u64 {
+        fn hash_versions<T>(hasher: &mut Fnv1a, versions: &BTreeMap<DataVersion, Vec<T>>) {
+            hasher.write_u64(versions.len() as u64);
+            for (version, values) in versions {
+                hasher.write_version(*version);
+                hasher.write_u64(values.len() as u64);
+            }
+        }
+
+        let mut hasher = Fnv1a::new();
+        hasher.write_u64(self.structure_converters.len() as u64);
+        for converter in &self.structure_converters {
+            hasher.write_version(converter.get_to_version());
+        }
+        hash_versions(&mut hasher, &self.structure_walkers);
+        hash_versions(&mut hasher, &self.try_structure_walkers);
+        hash_versions(&mut hasher, &self.structure_hooks);
+        hasher.write_u64(self.per_converter_hooks.len() as u64);
+        // only hashed when present, so that adding support for them kept existing fingerprints
+        if !self.terminal_converters.is_empty() {
+            hasher.write_u64(self.terminal_converters.len() as u64);
+            for converter in &self.terminal_converters {
+                hasher.write_version(converter.get_to_version());
+            }
+        }
+        // tagged, so that they can't be mistaken for the terminal converters or each other
+        if !self.downgrade_converters.is_empty() {
+            hasher.write_u64(1);
+            hasher.write_u64(self.downgrade_converters.len() as u64);
+            for converter in &self.downgrade_converters {
+                hasher.write_version(converter.get_to_version());
+            }
+        }
+        if !self.schemas.is_empty() {
+            hasher.write_u64(2);
+            hasher.write_u64(self.schemas.len() as u64);
+            for version in self.schemas.keys() {
+                hasher.write_version(*version);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Returns whether [`fingerprint`](Self::fingerprint) is `expected`, logging a warning if not.
+    pub fn verify_fingerprint(&self, expected: u64) -> bool {
+        let fingerprint = self.fingerprint();
+        if fingerprint != expected {
+            warn!(
+                "The converters of {} have changed: expected fingerprint {expected:016x}, got {fingerprint:016x}",
+                self.name
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Lists the converters, hooks and walkers that [`convert`](AbstractMapDataType::convert)
+    /// would run for the given versions, in the order they would run, without touching any data.
+    pub fn describe_plan(
+        &self,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) -> Vec<PlanStep> {
+        fn push_steps<T>(
+            plan: &mut Vec<PlanStep>,
+            kind: PlanStepKind,
+            entry: Option<(&DataVersion, &Vec<T>)>,
+        ) {
+            if let Some((&version, values)) = entry {
+                plan.extend(values.iter().map(|_| PlanStep {
+                    kind,
+                    version,
+                    label: None,
+                }));
+            }
+        }
+
+        let mut plan = Vec::new();
+        for converter in &self.structure_converters {
+            if converter.get_to_version() <= from_version {
+                continue;
+            }
+            if converter.get_to_version() > to_version {
+                break;
+            }
+
+            let per_converter_hooks =
+                Some((&converter.get_to_version(), &self.per_converter_hooks));
+            push_steps(&mut plan, PlanStepKind::PreHook, per_converter_hooks);
+            let hooks = self
+                .structure_hooks
+                .range(..=converter.get_to_version())
+                .next_back();
+            push_steps(&mut plan, PlanStepKind::PreHook, hooks);
+            plan.push(PlanStep {
+                kind: PlanStepKind::Converter,
+                version: converter.get_to_version(),
+                label: converter.label.clone(),
+            });
+            let hooks = self.structure_hooks.range(..=to_version).next_back();
+            push_steps(&mut plan, PlanStepKind::PostHook, hooks);
+            push_steps(&mut plan, PlanStepKind::PostHook, per_converter_hooks);
+        }
+
+        let hooks = self.structure_hooks.range(..=to_version).next_back();
+        push_steps(&mut plan, PlanStepKind::PreHook, hooks);
+        let walkers = self.structure_walkers.range(..=to_version).next_back();
+        push_steps(&mut plan, PlanStepKind::Walker, walkers);
+        let walkers = self.try_structure_walkers.range(..=to_version).next_back();
+        push_steps(&mut plan, PlanStepKind::Walker, walkers);
+        push_steps(&mut plan, PlanStepKind::PostHook, hooks);
+        plan.extend(
+            self.terminal_converters_between(from_version, to_version)
+                .iter()
+                .map(|converter| PlanStep {
+                    kind: PlanStepKind::Terminal,
+                    version: converter.get_to_version(),
+                    label: converter.label.clone(),
+                }),
+        );
+
+        plan
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum PlanStepKind {
+    Converter,
+    PreHook,
+    PostHook,
+    Walker,
+    Terminal,
+}
+
+impl Display for PlanStepKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PlanStepKind::Converter => "converter",
+            PlanStepKind::PreHook => "pre hook",
+            PlanStepKind::PostHook => "post hook",
+            PlanStepKind::Walker => "walker",
+            PlanStepKind::Terminal => "terminal converter",
+        })
+    }
+}
+
+/// A single step of a conversion plan. The version is the version the converter, hook or walker
+/// was registered at. Only converters can have labels.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PlanStep {
+    pub kind: PlanStepKind,
+    pub version: DataVersion,
+    pub label: Option<Rc<str>>,
+}
+
+impl Display for PlanStep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.kind, self.version.get_version())?;
+        if self.version.get_step() != 0 {
+            write!(f, ".{}", self.version.get_step())?;
+        }
+        if let Some(label) = &self.label {
+            write!(f, " ({label})")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> MapDataType<'a> {
+    /// Returns an iterator which runs one converter (and its hooks) per call to `next`, yielding
+    /// the version of that converter. After the last converter, the walkers are run and
+    /// `to_version` is yielded. If the iterator is dropped before it is exhausted, the data is left
+    /// at the version of the last converter that was run, without having been walked.
+    pub fn step_iter<'s>(
+        &'s self,
+        data: &'s mut JCompound,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) -> ConversionSteps<'s, 'a> {
+        let next_converter = self.next_converter_index(0, from_version);
+        ConversionSteps {
+            typ: self,
+            data,
+            from_version,
+            to_version,
+            next_converter,
+            walked: false,
+        }
+    }
+
+    /// Returns the index of the first converter at or after `start` which is after `from_version`.
+    fn next_converter_index(&self, start: usize, from_version: DataVersion) -> usize {
+        start
+            + self.structure_converters[start..]
+                .partition_point(|converter| converter.get_to_version() <= from_version)
+    }
+
+    /// Returns the `from_version` to use from now on, which the pre hooks may have adjusted.
+    fn run_converter(
+        &self,
+        converter: &MapDataConverter<DynMapDataConverterFunc<'a>>,
+        data: &mut JCompound,
+        mut from_version: DataVersion,
+        to_version: DataVersion,
+    ) -> DataVersion {
+        let observer = self.observer.as_deref();
+
+        // per converter hooks are reported to the observer with the version of the converter
+        for hook in &self.per_converter_hooks {
+            if let Some(adjusted) = hook.pre_hook_adjust(data, from_version, to_version) {
+                from_version = adjusted;
+            }
+            if let Some(observer) = observer {
+                observer.on_hook(converter.get_to_version());
+            }
+        }
+
+        let hooks = self
+            .structure_hooks
+            .range(..=converter.get_to_version())
+            .next_back();
+        if let Some((&hooks_version, hooks)) = hooks {
+            for hook in hooks {
+                if let Some(adjusted) = hook.pre_hook_adjust(data, from_version, to_version) {
+                    from_version = adjusted;
+                }
+                if let Some(observer) = observer {
+                    observer.on_hook(hooks_version);
+                }
+            }
+        }
+
+        if converter.get_to_version() > from_version {
+            converter.convert(data, from_version, to_version);
+            if let Some(observer) = observer {
+                observer.on_labelled_converter(converter.get_to_version(), converter.get_label());
+            }
+        }
+
+        // possibly new data format, update hooks
+        let hooks = self.structure_hooks.range(..=to_version).next_back();
+        if let Some((&hooks_version, hooks)) = hooks {
+            for hook in hooks.iter().rev() {
+                hook.post_hook(data, from_version, to_version);
+                if let Some(observer) = observer {
+                    observer.on_hook(hooks_version);
+                }
+            }
+        }
+
+        for hook in self.per_converter_hooks.iter().rev() {
+            hook.post_hook(data, from_version, to_version);
+            if let Some(observer) = observer {
+                observer.on_hook(converter.get_to_version());
+            }
+        }
+
+        from_version
+    }
 
+    /// Like [`convert`](AbstractMapDataType::convert), but stops running walkers at the first
+    /// error returned by a walker added with `add_try_structure_walker`. The post hooks are still
+    /// run before the error is returned, so that the data is left in a consistent format. `convert`
+    /// logs such errors instead.
+    pub fn try_convert(
+        &self,
+        data: &mut JCompound,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) -> Result<()> {
+        let mut from_version = from_version;
+        let mut index = self.next_converter_index(0, from_version);
+        while let Some(converter) = self.structure_converters.get(index) {
+            if converter.get_to_version() > to_version {
+                break;
+            }
+            from_version = self.run_converter(converter, data, from_version, to_version);
+            index = self.next_converter_index(index + 1, from_version);
+        }
+        self.run_walkers(data, from_version, to_version)
+    }
+
+    fn terminal_converters_between(
+        &self,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) -> &[MapDataConverter<DynMapDataConverterFunc<'a>>] {
+        let start = self
+            .terminal_converters
+            .partition_point(|converter| converter.get_to_version() <= from_version);
+        let end = self
+            .terminal_converters
+            .partition_point(|converter| converter.get_to_version() <= to_version);
+        &self.terminal_converters[start..end.max(start)]
+    }
+
+    fn run_walkers(
+        &self,
+        data: &mut JCompound,
+        mut from_version: DataVersion,
+        to_version: DataVersion,
+    ) -> Result<()> {
+        let observer = self.observer.as_deref();
+
+        let hooks = self.structure_hooks.range(..=to_version).next_back();
+        if let Some((&hooks_version, hooks)) = hooks {
+            for hook in hooks {
+                if let Some(adjusted) = hook.pre_hook_adjust(data, from_version, to_version) {
+                    from_version = adjusted;
+                }
+                if let Some(observer) = observer {
+                    observer.on_hook(hooks_version);
+                }
+            }
+        }
+
+        let walkers = self.structure_walkers.range(..=to_version).next_back();
+        if let Some((&walkers_version, walkers)) = walkers {
+            for walker in walkers {
+                walker.walk(data, from_version, to_version);
+                if let Some(observer) = observer {
+                    observer.on_walker(walkers_version);
+                }
+            }
+        }
+
+        let mut result = Ok(());
+        let walkers = self.try_structure_walkers.range(..=to_version).next_back();
+        if let Some((&walkers_version, walkers)) = walkers {
+            for walker in walkers {
+                result = walker.try_walk(data, from_version, to_version);
+                if let Some(observer) = observer {
+                    observer.on_walker(walkers_version);
+                }
+                if result.is_err() {
+                    break;
+                }
+            }
+        }
+
+        if let Some((&hooks_version, hooks)) = hooks {
+            for hook in hooks.iter().rev() {
+                hook.post_hook(data, from_version, to_version);
+                if let Some(observer) = observer {
+                    observer.on_hook(hooks_version);
+                }
+            }
+        }
+
+        if result.is_ok() {
+            for converter in self.terminal_converters_between(from_version, to_version) {
+                converter.convert(data, from_version, to_version);
+                if let Some(observer) = observer {
+                    observer.on_terminal_converter(converter.get_to_version());
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl<'a> AbstractMapDataType for MapDataType<'a> {
+    fn convert(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion) {
+        for _ in self.step_iter(data, from_version, to_version) {}
+    }
+}
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        for byte in value.to_le_bytes() {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_version(&mut self, version: DataVersion) {
+        self.write_u64(version.version.into());
+        self.write_u64(version.step.into());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// See [`MapDataType::step_iter`].
+pub struct ConversionSteps<'s, 'a> {
+    typ: &'s MapDataType<'a>,
+    data: &'s mut JCompound,
+    from_version: DataVersion,
+    to_version: DataVersion,
+    next_converter: usize,
+    walked: bool,
+}
+
+impl<'s, 'a> ConversionSteps<'s, 'a> {
+    pub fn data(&self) -> &JCompound {
+        self.data
+    }
+}
+
+impl<'s, 'a> Iterator for ConversionSteps<'s, 'a> {
+    type Item = DataVersion;
+
+    fn next(&mut self) -> Option<DataVersion> {
+        if self.walked {
+            return None;
+        }
+
+        if let Some(converter) = self.typ.structure_converters.get(self.next_converter) {
+            if converter.get_to_version() <= self.to_version {
+                self.from_version = self.typ.run_converter(
+                    converter,
+                    self.data,
+                    self.from_version,
+                    self.to_version,
+                );
+                self.next_converter = self
+                    .typ
+                    .next_converter_index(self.next_converter + 1, self.from_version);
+                return Some(converter.get_to_version());
+            }
+        }
+
+        self.walked = true;
+        if let Err(err) = self
+            .typ
+            .run_walkers(self.data, self.from_version, self.to_version)
+        {
+            warn!("Failed to walk {}: {err}", self.typ.name);
+        }
+        Some(self.to_version)
+    }
+}
+
+type DynValueDataConverterFunc<'a> = Rc<dyn ValueDataConverterFunc + 'a>;
+
+#[derive(Clone)]
 pub struct ObjectDataType<'a> {
     pub name: String,
     converters: Vec<ValueDataConverter<DynValueDataConverterFunc<'a>>>,
-    structure_hooks: BTreeMap<DataVersion, Vec<Box<dyn ValueDataHook + 'a>>>,
+    structure_hooks: BTreeMap<DataVersion, Vec<Rc<dyn ValueDataHook + 'a>>>,
+    delegate: Option<DynValueDataConverterFunc<'a>>,
 }
 structure_converters!(
     ObjectDataType,
     converters,
     ValueDataConverter,
-    ValueDataConverterFunc
+    ValueDataConverterFunc,
+    JValueMut
 );
 version_list!(
     ObjectDataType,
@@ -464,13 +1685,90 @@ version_list!(
     impl ValueDataHook + 'a
 );
 
+default_with_empty_name!(ObjectDataType);
+impl Debug for ObjectDataType<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectDataType")
+            .field("name", &self.name)
+            .field("converters", &self.converters)
+            .field("hooks", &debug_versions(&self.structure_hooks))
+            .field("has_delegate", &self.delegate.is_some())
+            .finish()
+    }
+}
+
 impl<'a> ObjectDataType<'a> {
     pub fn new(name: impl Into<String>) -> Self {
         Self {
             name: name.into(),
             converters: Vec::new(),
             structure_hooks: BTreeMap::new(),
+            delegate: None,
+        }
+    }
+
+    pub fn builder(name: impl Into<String>) -> ObjectDataTypeBuilder<'a> {
+        ObjectDataTypeBuilder {
+            typ: Self::new(name),
+            on_compound: None,
+            on_list: None,
+        }
+    }
+}
+
+/// Builds an [`ObjectDataType`] which delegates the conversion of compounds and lists to other
+/// types, see [`ObjectDataType::builder`]. Delegated conversions run after the type's own
+/// converters, whenever the value is converted to a later version.
+pub struct ObjectDataTypeBuilder<'a> {
+    typ: ObjectDataType<'a>,
+    on_compound: Option<Rc<dyn AbstractMapDataType + 'a>>,
+    on_list: Option<Rc<dyn AbstractValueDataType + 'a>>,
+}
+
+impl<'a> ObjectDataTypeBuilder<'a> {
+    /// Converts compound values with `map_type`.
+    pub fn on_compound(mut self, map_type: impl AbstractMapDataType + 'a) -> Self {
+        self.on_compound = Some(Rc::new(map_type));
+        self
+    }
+
+    /// Converts each element of list values with `element_type`.
+    pub fn on_list(mut self, element_type: impl AbstractValueDataType + 'a) -> Self {
+        self.on_list = Some(Rc::new(element_type));
+        self
+    }
+
+    /// Adds a converter to the type being built, for shapes not covered by the delegates.
+    pub fn converter(
+        mut self,
+        version: impl Into<DataVersion>,
+        converter_func: impl ValueDataConverterFunc + 'a,
+    ) -> Self {
+        self.typ.add_structure_converter(version, converter_func);
+        self
+    }
+
+    pub fn build(self) -> ObjectDataType<'a> {
+        let mut typ = self.typ;
+        let (on_compound, on_list) = (self.on_compound, self.on_list);
+        if on_compound.is_some() || on_list.is_some() {
+            typ.delegate = Some(Rc::new(value_data_converter_func(
+                move |data, from_version, to_version| match data {
+                    JValueMut::Compound(map) => {
+                        if let Some(map_type) = &on_compound {
+                            map_type.convert(map, from_version, to_version);
+                        }
+                    }
+                    JValueMut::List(list) => {
+                        if let Some(element_type) = &on_list {
+                            convert_object_list(&**element_type, list, from_version, to_version);
+                        }
+                    }
+                    _ => {}
+                },
+            )));
         }
+        typ
     }
 }
 
@@ -504,22 +1802,30 @@ impl<'a> AbstractValueDataType for ObjectDataType<'a> {
                 }
             }
         }
+
+        if let Some(delegate) = &self.delegate {
+            if from_version < to_version {
+                delegate.convert(data, from_version, to_version);
+            }
+        }
     }
 }
 
-type DynDynamicDataConverterFunc<'a> = Box<dyn DynamicDataConverterFunc + 'a>;
+type DynDynamicDataConverterFunc<'a> = Rc<dyn DynamicDataConverterFunc + 'a>;
 
+#[derive(Clone)]
 pub struct DynamicDataType<'a> {
     pub name: String,
     structure_converters: Vec<DynamicDataConverter<DynDynamicDataConverterFunc<'a>>>,
-    structure_walkers: BTreeMap<DataVersion, Vec<Box<dyn DynamicDataWalker + 'a>>>,
-    structure_hooks: BTreeMap<DataVersion, Vec<Box<dyn DynamicDataHook + 'a>>>,
+    structure_walkers: BTreeMap<DataVersion, Vec<Rc<dyn DynamicDataWalker + 'a>>>,
+    structure_hooks: BTreeMap<DataVersion, Vec<Rc<dyn DynamicDataHook + 'a>>>,
 }
 structure_converters!(
     DynamicDataType,
     structure_converters,
     DynamicDataConverter,
-    DynamicDataConverterFunc
+    DynamicDataConverterFunc,
+    JValue
 );
 version_list!(
     DynamicDataType,
@@ -527,6 +1833,18 @@ version_list!(
     structure_walkers,
     impl DynamicDataWalker + 'a
 );
+version_range_list!(
+    DynamicDataType,
+    add_walker_for_range,
+    structure_walkers,
+    DynamicDataWalker
+);
+walker_since!(
+    DynamicDataType,
+    structure_walkers,
+    DynamicDataWalker,
+    JValue
+);
 version_list!(
     DynamicDataType,
     add_structure_hook,
@@ -534,6 +1852,18 @@ version_list!(
     impl DynamicDataHook + 'a
 );
 
+default_with_empty_name!(DynamicDataType);
+impl Debug for DynamicDataType<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DynamicDataType")
+            .field("name", &self.name)
+            .field("converters", &self.structure_converters)
+            .field("walkers", &debug_versions(&self.structure_walkers))
+            .field("hooks", &debug_versions(&self.structure_hooks))
+            .finish()
+    }
+}
+
 impl<'a> DynamicDataType<'a> {
     pub fn new(name: impl Into<String>) -> Self {
         Self {
@@ -600,18 +1930,99 @@ impl<'a> AbstractDynamicDataType for DynamicDataType<'a> {
 
 type WalkersById<'a> = Vec<Rc<dyn MapDataWalker + 'a>>;
 
-pub struct IdDataType<'a> {
+/// The kind of id a [`GenericIdDataType`] identifies its data by: a string for [`IdDataType`] and
+/// a number for [`NumericIdDataType`].
+pub trait DataId: Ord + Clone + Debug + Borrow<Self::Ref> {
+    /// The form of the id which is read from the data, and passed to the id filter and unknown id
+    /// handler.
+    type Ref: ?Sized + Ord + ToOwned;
+
+    /// Returns the id stored at `id_key` in `data`, if it has one.
+    fn get<'d>(data: &'d JCompound, id_key: &JavaStr) -> Option<Cow<'d, Self::Ref>>;
+}
+
+impl DataId for JavaString {
+    type Ref = JavaStr;
+
+    fn get<'d>(data: &'d JCompound, id_key: &JavaStr) -> Option<Cow<'d, JavaStr>> {
+        match data.get(id_key)? {
+            JValue::String(id) => Some(Cow::Borrowed(id)),
+            _ => None,
+        }
+    }
+}
+
+/// The id may be stored as any integer type that fits in an `i32`, with bytes read as unsigned.
+impl DataId for i32 {
+    type Ref = i32;
+
+    fn get<'d>(data: &'d JCompound, id_key: &JavaStr) -> Option<Cow<'d, i32>> {
+        let id = match *data.get(id_key)? {
+            JValue::Byte(id) => (id as u8).into(),
+            JValue::Short(id) => id.into(),
+            JValue::Int(id) => id,
+            JValue::Long(id) => i32::try_from(id).ok()?,
+            _ => return None,
+        };
+        Some(Cow::Owned(id))
+    }
+}
+
+type IdFilter<'a, K> = Rc<dyn Fn(&<K as DataId>::Ref) -> bool + 'a>;
+type UnknownIdHandler<'a, K> = Rc<dyn Fn(&<K as DataId>::Ref) + 'a>;
+type IdSplitterFunc<'a> = Rc<dyn Fn(JCompound, DataVersion, DataVersion) -> Vec<JCompound> + 'a>;
+
+/// What an [`IdDataType`] skips for data whose id is rejected by its id filter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IdFilterScope {
+    /// Only the id-specific and default id walkers are skipped.
+    #[default]
+    IdWalkers,
+    /// All walkers, and the hooks around them, are skipped. Converters still run.
+    Walkers,
+    /// The data is left untouched. Converters added with `add_converter_for_id` are ordinary
+    /// structure converters, so this is the only scope which skips them.
+    Everything,
+}
+
+#[derive(Clone)]
+struct IdSplitter<'a, K> {
+    version: DataVersion,
+    id: K,
+    func: IdSplitterFunc<'a>,
+}
+
+/// A data type which runs different walkers depending on the id of the data, stored at `id_key`.
+/// Use it through [`IdDataType`] or [`NumericIdDataType`].
+#[derive(Clone)]
+pub struct GenericIdDataType<'a, K: DataId> {
     pub name: String,
+    id_key: JavaString,
     structure_converters: Vec<MapDataConverter<DynMapDataConverterFunc<'a>>>,
-    structure_walkers: BTreeMap<DataVersion, Vec<Box<dyn MapDataWalker + 'a>>>,
-    structure_hooks: BTreeMap<DataVersion, Vec<Box<dyn MapDataHook + 'a>>>,
-    walkers_by_id: BTreeMap<JavaString, BTreeMap<DataVersion, WalkersById<'a>>>,
+    structure_walkers: BTreeMap<DataVersion, Vec<Rc<dyn MapDataWalker + 'a>>>,
+    structure_hooks: BTreeMap<DataVersion, Vec<Rc<dyn MapDataHook + 'a>>>,
+    walkers_by_id: BTreeMap<K, BTreeMap<DataVersion, WalkersById<'a>>>,
+    default_id_walkers: BTreeMap<DataVersion, WalkersById<'a>>,
+    unknown_id_handler: Option<UnknownIdHandler<'a, K>>,
+    id_filter: Option<IdFilter<'a, K>>,
+    id_filter_scope: IdFilterScope,
+    splitters: Vec<IdSplitter<'a, K>>,
 }
+
+/// A data type which runs different walkers depending on the string `"id"` of the data.
+pub type IdDataType<'a> = GenericIdDataType<'a, JavaString>;
+
+/// Like [`IdDataType`], but for legacy data which is identified by a numeric id at `id_key`
+/// instead of a string `"id"`. The id may be stored as any integer type that fits in an `i32`, with
+/// bytes read as unsigned.
+pub type NumericIdDataType<'a> = GenericIdDataType<'a, i32>;
+
 structure_converters!(
     IdDataType,
     structure_converters,
     MapDataConverter,
-    MapDataConverterFunc
+    MapDataConverterFunc,
+    JCompound
 );
 version_list!(
     IdDataType,
@@ -619,46 +2030,296 @@ version_list!(
     structure_walkers,
     impl MapDataWalker + 'a
 );
+version_range_list!(
+    IdDataType,
+    add_walker_for_range,
+    structure_walkers,
+    MapDataWalker
+);
+walker_since!(IdDataType, structure_walkers, MapDataWalker, JCompound);
 version_list!(
     IdDataType,
     add_structure_hook,
     structure_hooks,
     impl MapDataHook + 'a
 );
+structure_converters!(
+    NumericIdDataType,
+    structure_converters,
+    MapDataConverter,
+    MapDataConverterFunc,
+    JCompound
+);
+version_list!(
+    NumericIdDataType,
+    add_structure_walker,
+    structure_walkers,
+    impl MapDataWalker + 'a
+);
+version_range_list!(
+    NumericIdDataType,
+    add_walker_for_range,
+    structure_walkers,
+    MapDataWalker
+);
+walker_since!(
+    NumericIdDataType,
+    structure_walkers,
+    MapDataWalker,
+    JCompound
+);
+version_list!(
+    NumericIdDataType,
+    add_structure_hook,
+    structure_hooks,
+    impl MapDataHook + 'a
+);
+
+default_with_empty_name!(IdDataType);
+impl Debug for IdDataType<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_debug("IdDataType", f)
+    }
+}
+
+impl Debug for NumericIdDataType<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_debug("NumericIdDataType", f)
+    }
+}
 
 impl<'a> IdDataType<'a> {
     pub fn new(name: impl Into<String>) -> Self {
+        Self::with_id_key(name, "id")
+    }
+
+    /// Returns the ids which have walkers registered at any version, in order.
+    pub fn walker_ids(&self) -> impl Iterator<Item = &JavaStr> {
+        self.walkers_by_id.keys().map(|id| &id[..])
+    }
+
+    pub fn copy_walkers(
+        &mut self,
+        version: impl Into<DataVersion> + Clone,
+        from_id: impl AsRef<JavaStr>,
+        to_id: impl Into<JavaString> + Clone,
+    ) {
+        self.copy_walkers_between(version.into(), from_id.as_ref(), to_id.into());
+    }
+}
+
+impl<'a> NumericIdDataType<'a> {
+    pub fn new(name: impl Into<String>, id_key: impl Into<JavaString>) -> Self {
+        Self::with_id_key(name, id_key)
+    }
+
+    /// Returns the id of `data`, if it has one.
+    pub fn get_id(&self, data: &JCompound) -> Option<i32> {
+        i32::get(data, &self.id_key).map(Cow::into_owned)
+    }
+
+    /// Returns the ids which have walkers registered at any version, in order.
+    pub fn walker_ids(&self) -> impl Iterator<Item = i32> + '_ {
+        self.walkers_by_id.keys().copied()
+    }
+
+    pub fn copy_walkers(&mut self, version: impl Into<DataVersion>, from_id: i32, to_id: i32) {
+        self.copy_walkers_between(version.into(), &from_id, to_id);
+    }
+}
+
+impl<'a, K: DataId + 'a> GenericIdDataType<'a, K> {
+    fn with_id_key(name: impl Into<String>, id_key: impl Into<JavaString>) -> Self {
         Self {
             name: name.into(),
+            id_key: id_key.into(),
             structure_converters: Vec::new(),
             structure_walkers: BTreeMap::new(),
             structure_hooks: BTreeMap::new(),
             walkers_by_id: BTreeMap::new(),
+            default_id_walkers: BTreeMap::new(),
+            unknown_id_handler: None,
+            id_filter: None,
+            id_filter_scope: IdFilterScope::default(),
+            splitters: Vec::new(),
         }
     }
 
+    fn fmt_debug(&self, type_name: &str, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct(type_name)
+            .field("name", &self.name)
+            .field("converters", &self.structure_converters)
+            .field("walkers", &debug_versions(&self.structure_walkers))
+            .field("hooks", &debug_versions(&self.structure_hooks))
+            .field("ids", &self.walkers_by_id.len())
+            .field(
+                "default_id_walkers",
+                &debug_versions(&self.default_id_walkers),
+            )
+            .field(
+                "splitters",
+                &self
+                    .splitters
+                    .iter()
+                    .map(|splitter| (splitter.version, &splitter.id))
+                    .collect::<Vec<_>>(),
+            )
+            .field("id_filter_scope", &self.id_filter_scope)
+            .field("id_key", &self.id_key)
+            .finish_non_exhaustive()
+    }
+
+    pub fn id_key(&self) -> &JavaStr {
+        &self.id_key
+    }
+
+    /// Only processes data whose id is accepted by `filter`, skipping what the
+    /// [scope](Self::set_id_filter_scope) selects for other data. The filter sees the id from
+    /// before conversion. Data without an id is not filtered.
+    pub fn set_id_filter(&mut self, filter: impl Fn(&K::Ref) -> bool + 'a) {
+        self.id_filter = Some(Rc::new(filter));
+    }
+
+    pub fn set_id_filter_scope(&mut self, scope: IdFilterScope) {
+        self.id_filter_scope = scope;
+    }
+
+    /// Sets a handler that is called with the id of any data whose id has no walkers registered
+    /// at or below the version being converted to, e.g. to log or collect ids missing coverage.
+    pub fn set_unknown_id_handler(&mut self, handler: impl Fn(&K::Ref) + 'a) {
+        self.unknown_id_handler = Some(Rc::new(handler));
+    }
+
     pub fn add_converter_for_id(
         &mut self,
-        id: impl Into<JavaString>,
+        id: impl Into<K>,
         version: impl Into<DataVersion>,
         converter_func: impl MapDataConverterFunc + 'a,
     ) {
-        let id_str = id.into();
-        self.add_structure_converter(
-            version,
-            map_data_converter_func(move |data, from_version, to_version| {
-                if matches!(data.get("id"), Some(valence_nbt::Value::String(str)) if str == &id_str)
-                {
+        let id = id.into();
+        let id_key = self.id_key.clone();
+        let dyn_rc: Rc<dyn MapDataConverterFunc> = Rc::new(map_data_converter_func(
+            move |data, from_version, to_version| {
+                if K::get(data, &id_key).is_some_and(|data_id| *data_id == *id.borrow()) {
                     converter_func.convert(data, from_version, to_version);
                 }
-            }),
+            },
+        ));
+        let converter = MapDataConverter::new(version, dyn_rc);
+        // insert after any converters with the same version, so they run in registration order
+        let index = self
+            .structure_converters
+            .partition_point(|c| c <= &converter);
+        self.structure_converters.insert(index, converter);
+    }
+
+    /// Adds a converter which replaces data with the given id by any number of compounds, e.g. to
+    /// split a combined entity into separate ones. It receives the data already converted to
+    /// `version`, and the compounds it returns are treated as being at `version`. Splitters only
+    /// run through [`convert_split`](Self::convert_split), as [`AbstractMapDataType::convert`]
+    /// can only modify data in place.
+    pub fn add_splitter_for_id(
+        &mut self,
+        id: impl Into<K>,
+        version: impl Into<DataVersion>,
+        splitter: impl Fn(JCompound, DataVersion, DataVersion) -> Vec<JCompound> + 'a,
+    ) {
+        let version = version.into();
+        let index = self
+            .splitters
+            .partition_point(|splitter| splitter.version <= version);
+        self.splitters.insert(
+            index,
+            IdSplitter {
+                version,
+                id: id.into(),
+                func: Rc::new(splitter),
+            },
         );
     }
 
+    /// Converts `data`, running any splitters along the way, and returns the compounds which
+    /// replace it. Without a matching splitter this is the converted `data` on its own.
+    ///
+    /// The converters run in windows between the splitter versions, but like in
+    /// [`convert`](AbstractMapDataType::convert) they are always passed `from_version` and
+    /// `to_version`. The walkers run once on each resulting compound, after all the converters.
+    /// Compounds produced by a splitter are checked against the id filter by their own id.
+    pub fn convert_split(
+        &self,
+        data: JCompound,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) -> Vec<JCompound> {
+        let untouched =
+            |filtered_out: bool| filtered_out && self.id_filter_scope == IdFilterScope::Everything;
+
+        // each piece keeps the from version its hooks adjusted it to
+        let filtered_out = self.is_filtered_out(&data);
+        let mut pieces = vec![(data, from_version, filtered_out)];
+        let mut converted_version = from_version;
+        let start = self
+            .splitters
+            .partition_point(|splitter| splitter.version <= from_version);
+        for splitter in &self.splitters[start..] {
+            if splitter.version > to_version {
+                break;
+            }
+            for (piece, piece_from_version, filtered_out) in &mut pieces {
+                if untouched(*filtered_out) {
+                    continue;
+                }
+                self.run_converters(
+                    piece,
+                    converted_version,
+                    splitter.version,
+                    piece_from_version,
+                    to_version,
+                );
+            }
+            converted_version = splitter.version;
+            pieces = pieces
+                .into_iter()
+                .flat_map(|(piece, piece_from_version, filtered_out)| {
+                    if !untouched(filtered_out)
+                        && K::get(&piece, &self.id_key)
+                            .is_some_and(|id| *id == *splitter.id.borrow())
+                    {
+                        (splitter.func)(piece, from_version, to_version)
+                            .into_iter()
+                            .map(|piece| {
+                                let filtered_out = self.is_filtered_out(&piece);
+                                (piece, piece_from_version, filtered_out)
+                            })
+                            .collect()
+                    } else {
+                        vec![(piece, piece_from_version, filtered_out)]
+                    }
+                })
+                .collect();
+        }
+        pieces
+            .into_iter()
+            .map(|(mut piece, mut piece_from_version, filtered_out)| {
+                if !untouched(filtered_out) {
+                    self.run_converters(
+                        &mut piece,
+                        converted_version,
+                        to_version,
+                        &mut piece_from_version,
+                        to_version,
+                    );
+                    self.run_walkers(&mut piece, piece_from_version, to_version, filtered_out);
+                }
+                piece
+            })
+            .collect()
+    }
+
     pub fn add_walker_for_id(
         &mut self,
         version: impl Into<DataVersion>,
-        id: impl Into<JavaString>,
+        id: impl Into<K>,
         walker: impl MapDataWalker + 'a,
     ) {
         self.walkers_by_id
@@ -669,21 +2330,29 @@ impl<'a> IdDataType<'a> {
             .push(Rc::new(walker));
     }
 
-    pub fn copy_walkers(
+    /// Adds a walker that runs in place of the id-specific walkers when the data has no id, or its
+    /// id has no walkers registered at or below the version being converted to. It never runs
+    /// alongside id-specific walkers. The structure walkers are unaffected and always run first,
+    /// whatever the id.
+    pub fn add_default_id_walker(
         &mut self,
-        version: impl Into<DataVersion> + Clone,
-        from_id: impl AsRef<JavaStr>,
-        to_id: impl Into<JavaString> + Clone,
+        version: impl Into<DataVersion>,
+        walker: impl MapDataWalker + 'a,
     ) {
-        if let Some(from_versions) = self.walkers_by_id.get(from_id.as_ref()) {
-            if let Some((_, from_walkers)) =
-                from_versions.range(..=version.clone().into()).next_back()
-            {
+        self.default_id_walkers
+            .entry(version.into())
+            .or_default()
+            .push(Rc::new(walker));
+    }
+
+    fn copy_walkers_between(&mut self, version: DataVersion, from_id: &K::Ref, to_id: K) {
+        if let Some(from_versions) = self.walkers_by_id.get(from_id) {
+            if let Some((_, from_walkers)) = from_versions.range(..=version).next_back() {
                 for walker in from_walkers.clone() {
                     self.walkers_by_id
-                        .entry(to_id.clone().into())
+                        .entry(to_id.clone())
                         .or_default()
-                        .entry(version.clone().into())
+                        .entry(version)
                         .or_default()
                         .push(walker);
                 }
@@ -692,13 +2361,29 @@ impl<'a> IdDataType<'a> {
     }
 }
 
-impl<'a> AbstractMapDataType for IdDataType<'a> {
-    fn convert(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion) {
+impl<'a, K: DataId> GenericIdDataType<'a, K> {
+    fn is_filtered_out(&self, data: &JCompound) -> bool {
+        match (&self.id_filter, K::get(data, &self.id_key)) {
+            (Some(filter), Some(id)) => !filter(&id),
+            _ => false,
+        }
+    }
+
+    /// Runs the converters after `after_version`, up to and including `up_to_version`. They are
+    /// passed `from_version`, which the hooks may adjust, and `to_version`.
+    fn run_converters(
+        &self,
+        data: &mut JCompound,
+        after_version: DataVersion,
+        up_to_version: DataVersion,
+        from_version: &mut DataVersion,
+        to_version: DataVersion,
+    ) {
         for converter in &self.structure_converters {
-            if converter.get_to_version() <= from_version {
+            if converter.get_to_version() <= after_version.max(*from_version) {
                 continue;
             }
-            if converter.get_to_version() > to_version {
+            if converter.get_to_version() > up_to_version {
                 break;
             }
 
@@ -708,27 +2393,45 @@ impl<'a> AbstractMapDataType for IdDataType<'a> {
                 .next_back();
             if let Some((_, hooks)) = hooks {
                 for hook in hooks {
-                    hook.pre_hook(data, from_version, to_version);
+                    if let Some(adjusted) = hook.pre_hook_adjust(data, *from_version, to_version) {
+                        *from_version = adjusted;
+                    }
                 }
             }
 
-            converter.convert(data, from_version, to_version);
+            if converter.get_to_version() > *from_version {
+                converter.convert(data, *from_version, to_version);
+            }
 
             // possibly new data format, update hooks
             let hooks = self.structure_hooks.range(..=to_version).next_back();
             if let Some((_, hooks)) = hooks {
                 for hook in hooks {
-                    hook.post_hook(data, from_version, to_version);
+                    hook.post_hook(data, *from_version, to_version);
                 }
             }
         }
+    }
+
+    fn run_walkers(
+        &self,
+        data: &mut JCompound,
+        mut from_version: DataVersion,
+        to_version: DataVersion,
+        filtered_out: bool,
+    ) {
+        if filtered_out && self.id_filter_scope == IdFilterScope::Walkers {
+            return;
+        }
 
         // run pre hooks
 
         let hooks = self.structure_hooks.range(..=to_version).next_back();
         if let Some((_, hooks)) = hooks {
             for hook in hooks.iter().rev() {
-                hook.pre_hook(data, from_version, to_version);
+                if let Some(adjusted) = hook.pre_hook_adjust(data, from_version, to_version) {
+                    from_version = adjusted;
+                }
             }
         }
 
@@ -741,13 +2444,32 @@ impl<'a> AbstractMapDataType for IdDataType<'a> {
             }
         }
 
-        if let Some(valence_nbt::Value::String(id)) = data.get("id") {
-            if let Some(walkers_by_version) = self.walkers_by_id.get(id) {
-                if let Some((_, walkers)) = walkers_by_version.range(..=to_version).next_back() {
-                    for walker in walkers {
-                        walker.walk(data, from_version, to_version);
+        let id_walkers = match K::get(data, &self.id_key) {
+            _ if filtered_out => None,
+            Some(id) => {
+                let walkers = self.walkers_by_id.get(&*id).and_then(|walkers_by_version| {
+                    walkers_by_version.range(..=to_version).next_back()
+                });
+                if walkers.is_none() {
+                    if let Some(unknown_id_handler) = &self.unknown_id_handler {
+                        unknown_id_handler(&id);
                     }
                 }
+                walkers
+            }
+            None => None,
+        };
+        // default id walkers only run when no id-specific walkers matched, so nothing is walked twice
+        let id_walkers = id_walkers.or_else(|| {
+            if filtered_out {
+                None
+            } else {
+                self.default_id_walkers.range(..=to_version).next_back()
+            }
+        });
+        if let Some((_, walkers)) = id_walkers {
+            for walker in walkers {
+                walker.walk(data, from_version, to_version);
             }
         }
 
@@ -761,9 +2483,395 @@ impl<'a> AbstractMapDataType for IdDataType<'a> {
     }
 }
 
+impl<'a, K: DataId> AbstractMapDataType for GenericIdDataType<'a, K> {
+    fn convert(
+        &self,
+        data: &mut JCompound,
+        mut from_version: DataVersion,
+        to_version: DataVersion,
+    ) {
+        let filtered_out = self.is_filtered_out(data);
+        if filtered_out && self.id_filter_scope == IdFilterScope::Everything {
+            return;
+        }
+
+        self.run_converters(
+            data,
+            from_version,
+            to_version,
+            &mut from_version,
+            to_version,
+        );
+        self.run_walkers(data, from_version, to_version, filtered_out);
+    }
+}
+
+/// Returns a type which converts with `first` and then with `second`, using the same versions.
+pub fn sequence(
+    first: impl AbstractMapDataType,
+    second: impl AbstractMapDataType,
+) -> impl AbstractMapDataType {
+    struct Sequence<A, B>(A, B);
+    impl<A: AbstractMapDataType, B: AbstractMapDataType> AbstractMapDataType for Sequence<A, B> {
+        fn convert(
+            &self,
+            data: &mut JCompound,
+            from_version: DataVersion,
+            to_version: DataVersion,
+        ) {
+            self.0.convert(data, from_version, to_version);
+            self.1.convert(data, from_version, to_version);
+        }
+    }
+
+    Sequence(first, second)
+}
+
+/// Returns a type which converts with each of `types` in order, using the same versions.
+pub fn sequence_all<'a>(
+    types: Vec<Box<dyn AbstractMapDataType + 'a>>,
+) -> impl AbstractMapDataType + 'a {
+    struct SequenceAll<'a>(Vec<Box<dyn AbstractMapDataType + 'a>>);
+    impl AbstractMapDataType for SequenceAll<'_> {
+        fn convert(
+            &self,
+            data: &mut JCompound,
+            from_version: DataVersion,
+            to_version: DataVersion,
+        ) {
+            for typ in &self.0 {
+                typ.convert(data, from_version, to_version);
+            }
+        }
+    }
+
+    SequenceAll(types)
+}
+
+/// Data which is converted the first time it is accessed, for workloads where most data is
+/// loaded but never looked at.
+///
+/// The first access of any kind, shared or mutable, converts the data and caches the result.
+/// From then on this behaves as the converted compound, and is never converted again. Dropping
+/// it before any access skips the conversion entirely. If the conversion panics, the data is lost
+/// and every later access panics as well.
+pub struct LazyConverted<'a> {
+    unconverted: Cell<Option<JCompound>>,
+    converted: OnceCell<JCompound>,
+    data_type: &'a (dyn AbstractMapDataType + 'a),
+    from_version: DataVersion,
+    to_version: DataVersion,
+}
+
+impl<'a> LazyConverted<'a> {
+    pub fn new(
+        data: JCompound,
+        data_type: &'a (dyn AbstractMapDataType + 'a),
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) -> Self {
+        Self {
+            unconverted: Cell::new(Some(data)),
+            converted: OnceCell::new(),
+            data_type,
+            from_version,
+            to_version,
+        }
+    }
+
+    pub fn is_converted(&self) -> bool {
+        self.converted.get().is_some()
+    }
+
+    pub fn get(&self) -> &JCompound {
+        self.converted.get_or_init(|| {
+            let mut data = self
+                .unconverted
+                .take()
+                .expect("an earlier conversion of the data panicked");
+            self.data_type
+                .convert(&mut data, self.from_version, self.to_version);
+            data
+        })
+    }
+
+    pub fn get_mut(&mut self) -> &mut JCompound {
+        self.get();
+        self.converted.get_mut().expect("data was just converted")
+    }
+
+    pub fn into_inner(self) -> JCompound {
+        self.get();
+        self.converted
+            .into_inner()
+            .expect("data was just converted")
+    }
+}
+
+impl Deref for LazyConverted<'_> {
+    type Target = JCompound;
+
+    fn deref(&self) -> &JCompound {
+        self.get()
+    }
+}
+
+impl DerefMut for LazyConverted<'_> {
+    fn deref_mut(&mut self) -> &mut JCompound {
+        self.get_mut()
+    }
+}
+
+/// A tagged union of compounds, which are converted by the [`MapDataType`] registered for the
+/// string under their discriminant key. The variant is chosen from the data before conversion;
+/// data with a missing or unregistered discriminant is left untouched.
+pub struct SumTypeDataType<'a> {
+    pub name: String,
+    discriminant_key: JavaString,
+    variants: BTreeMap<JavaString, MapDataType<'a>>,
+}
+
+impl Debug for SumTypeDataType<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SumTypeDataType")
+            .field("name", &self.name)
+            .field("discriminant_key", &self.discriminant_key)
+            .field("variants", &self.variants)
+            .finish()
+    }
+}
+
+impl<'a> SumTypeDataType<'a> {
+    pub fn new(name: impl Into<String>, discriminant_key: impl Into<JavaString>) -> Self {
+        Self {
+            name: name.into(),
+            discriminant_key: discriminant_key.into(),
+            variants: BTreeMap::new(),
+        }
+    }
+
+    pub fn discriminant_key(&self) -> &JavaStr {
+        &self.discriminant_key
+    }
+
+    /// Registers the type of the variant with the given discriminant, replacing any previous one.
+    pub fn add_variant(&mut self, discriminant: impl Into<JavaString>, typ: MapDataType<'a>) {
+        self.variants.insert(discriminant.into(), typ);
+    }
+
+    pub fn variant(&self, discriminant: impl AsRef<JavaStr>) -> Option<&MapDataType<'a>> {
+        self.variants.get(discriminant.as_ref())
+    }
+
+    pub fn variant_mut(
+        &mut self,
+        discriminant: impl AsRef<JavaStr>,
+    ) -> Option<&mut MapDataType<'a>> {
+        self.variants.get_mut(discriminant.as_ref())
+    }
+
+    pub fn variant_names(&self) -> impl Iterator<Item = &JavaStr> {
+        self.variants.keys().map(|discriminant| &discriminant[..])
+    }
+}
+
+impl<'a> AbstractMapDataType for SumTypeDataType<'a> {
+    fn convert(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion) {
+        let Some(JValue::String(discriminant)) = data.get(&self.discriminant_key[..]) else {
+            return;
+        };
+        if let Some(variant) = self.variants.get(discriminant) {
+            variant.convert(data, from_version, to_version);
+        }
+    }
+}
+
+type ContextualFunc<'a, C> = Rc<dyn Fn(&mut JCompound, &C, DataVersion, DataVersion) + 'a>;
+
+/// A map data type whose converters and walkers are passed a shared context, such as a lookup table
+/// loaded once, instead of each capturing their own copy. `C` may be unsized, e.g. `dyn Any`.
+///
+/// The context is passed explicitly to [`convert`](Self::convert). To use the type anywhere a plain
+/// data type is expected, including the `convert_*_in_map` helpers, bind a context to it with
+/// [`with_context`](Self::with_context). Walkers can thread their context through to nested types
+/// this way, e.g. `convert_map_in_map(nested.with_context(context), data, "key", from, to)`.
+///
+/// Only converters and walkers are supported: converting runs the converters after the from
+/// version and up to the to version, in order, then the walkers for the to version. Unlike
+/// [`MapDataType`] there are no hooks, try walkers, terminal or downgrade converters, observers or
+/// step-by-step conversion, since the converters of a `MapDataType` can't be passed a context. Work
+/// that would be done by a hook has to be done by the converters and walkers themselves.
+pub struct ContextualMapDataType<'a, C: ?Sized> {
+    pub name: String,
+    structure_converters: Vec<(DataVersion, ContextualFunc<'a, C>)>,
+    structure_walkers: BTreeMap<DataVersion, Vec<ContextualFunc<'a, C>>>,
+}
+
+impl<'a, C: ?Sized> Clone for ContextualMapDataType<'a, C> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            structure_converters: self.structure_converters.clone(),
+            structure_walkers: self.structure_walkers.clone(),
+        }
+    }
+}
+
+impl<C: ?Sized> Default for ContextualMapDataType<'_, C> {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+impl<C: ?Sized> Debug for ContextualMapDataType<'_, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextualMapDataType")
+            .field("name", &self.name)
+            .field(
+                "converters",
+                &self
+                    .structure_converters
+                    .iter()
+                    .map(|(version, _)| *version)
+                    .collect::<Vec<_>>(),
+            )
+            .field("walkers", &debug_versions(&self.structure_walkers))
+            .finish()
+    }
+}
+
+impl<'a, C: ?Sized> ContextualMapDataType<'a, C> {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            structure_converters: Vec::new(),
+            structure_walkers: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_structure_converter(
+        &mut self,
+        version: impl Into<DataVersion>,
+        converter_func: impl Fn(&mut JCompound, &C, DataVersion, DataVersion) + 'a,
+    ) {
+        let version = version.into();
+        // insert after any converters with the same version, so they run in registration order
+        let index = self
+            .structure_converters
+            .partition_point(|(converter_version, _)| *converter_version <= version);
+        self.structure_converters
+            .insert(index, (version, Rc::new(converter_func)));
+    }
+
+    pub fn add_structure_walker(
+        &mut self,
+        version: impl Into<DataVersion>,
+        walker: impl Fn(&mut JCompound, &C, DataVersion, DataVersion) + 'a,
+    ) {
+        self.structure_walkers
+            .entry(version.into())
+            .or_default()
+            .push(Rc::new(walker));
+    }
+
+    pub fn convert(
+        &self,
+        data: &mut JCompound,
+        context: &C,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) {
+        let start = self
+            .structure_converters
+            .partition_point(|(version, _)| *version <= from_version);
+        for (version, converter) in &self.structure_converters[start..] {
+            if *version > to_version {
+                break;
+            }
+            converter(data, context, from_version, to_version);
+        }
+
+        if let Some((_, walkers)) = self.structure_walkers.range(..=to_version).next_back() {
+            for walker in walkers {
+                walker(data, context, from_version, to_version);
+            }
+        }
+    }
+
+    pub fn with_context<'s>(&'s self, context: &'s C) -> impl AbstractMapDataType + 's {
+        struct WithContext<'s, 'a, C: ?Sized> {
+            typ: &'s ContextualMapDataType<'a, C>,
+            context: &'s C,
+        }
+        impl<C: ?Sized> AbstractMapDataType for WithContext<'_, '_, C> {
+            fn convert(
+                &self,
+                data: &mut JCompound,
+                from_version: DataVersion,
+                to_version: DataVersion,
+            ) {
+                self.typ
+                    .convert(data, self.context, from_version, to_version);
+            }
+        }
+        WithContext { typ: self, context }
+    }
+}
+
+#[derive(Default)]
+pub struct TypeRegistry<'a> {
+    types: HashMap<String, Box<dyn AbstractMapDataType + 'a>>,
+}
+
+impl<'a> TypeRegistry<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, name: impl Into<String>, typ: impl AbstractMapDataType + 'a) {
+        self.types.insert(name.into(), Box::new(typ));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&(dyn AbstractMapDataType + 'a)> {
+        self.types.get(name).map(|typ| &**typ)
+    }
+
+    pub fn convert_named(
+        &self,
+        name: &str,
+        data: &mut JCompound,
+        from_version: impl Into<DataVersion>,
+        to_version: impl Into<DataVersion>,
+    ) -> Result<()> {
+        let typ = self.get(name).ok_or_else(|| ConversionError {
+            message: format!("Unknown data type {name}"),
+        })?;
+        typ.convert(data, from_version.into(), to_version.into());
+        Ok(())
+    }
+}
+
 pub trait MapDataHook {
     fn pre_hook(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion);
     fn post_hook(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion);
+
+    /// Runs the pre hook, optionally returning a corrected `from_version`, e.g. for data that was
+    /// stamped with the wrong version. The engine always calls this rather than `pre_hook`.
+    ///
+    /// The corrected version is passed to the rest of the hooks, converters and walkers in this
+    /// conversion. If the hook runs before a converter that the corrected version has already
+    /// passed, that converter is skipped (its post hooks still run), as are any later ones the
+    /// corrected version has passed. Converters which have already run are never re-run, so a
+    /// lower version only changes the `from_version` seen by what runs afterwards.
+    fn pre_hook_adjust(
+        &self,
+        data: &mut JCompound,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) -> Option<DataVersion> {
+        self.pre_hook(data, from_version, to_version);
+        None
+    }
 }
 
 pub trait ValueDataHook {
@@ -776,6 +2884,74 @@ pub trait DynamicDataHook {
     fn post_hook(&self, data: &mut JValue, from_version: DataVersion, to_version: DataVersion);
 }
 
+pub trait TryMapDataWalker {
+    fn try_walk(
+        &self,
+        data: &mut JCompound,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) -> Result<()>;
+}
+
+pub fn try_map_data_walker<'a, F>(func: F) -> impl TryMapDataWalker + 'a
+where
+    F: Fn(&mut JCompound, DataVersion, DataVersion) -> Result<()> + 'a,
+{
+    struct TryMapDataWalkerImpl<F>(F);
+    impl<F> TryMapDataWalker for TryMapDataWalkerImpl<F>
+    where
+        F: Fn(&mut JCompound, DataVersion, DataVersion) -> Result<()>,
+    {
+        fn try_walk(
+            &self,
+            data: &mut JCompound,
+            from_version: DataVersion,
+            to_version: DataVersion,
+        ) -> Result<()> {
+            (self.0)(data, from_version, to_version)
+        }
+    }
+    TryMapDataWalkerImpl(func)
+}
+
+/// Receives a notification after each converter, walker and hook is run, with the version it was
+/// registered at. All methods do nothing by default.
+pub trait ConversionObserver {
+    fn on_converter(&self, _version: DataVersion) {}
+    fn on_walker(&self, _version: DataVersion) {}
+    fn on_hook(&self, _version: DataVersion) {}
+
+    /// Called instead of `on_converter`, with the label of the converter if it has one. Defaults
+    /// to calling `on_converter`.
+    fn on_labelled_converter(&self, version: DataVersion, _label: Option<&str>) {
+        self.on_converter(version)
+    }
+    /// Called for each converter added with `add_terminal_converter`, with its minimum version.
+    fn on_terminal_converter(&self, _version: DataVersion) {}
+}
+
+impl<T: ConversionObserver + ?Sized> ConversionObserver for &T {
+    fn on_converter(&self, version: DataVersion) {
+        T::on_converter(self, version)
+    }
+
+    fn on_labelled_converter(&self, version: DataVersion, label: Option<&str>) {
+        T::on_labelled_converter(self, version, label)
+    }
+
+    fn on_terminal_converter(&self, version: DataVersion) {
+        T::on_terminal_converter(self, version)
+    }
+
+    fn on_walker(&self, version: DataVersion) {
+        T::on_walker(self, version)
+    }
+
+    fn on_hook(&self, version: DataVersion) {
+        T::on_hook(self, version)
+    }
+}
+
 pub trait MapDataWalker {
     fn walk(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion);
 }
@@ -796,6 +2972,57 @@ where
     MapDataWalkerImpl(func)
 }
 
+/// The compound containing the data being walked. The walked data is temporarily taken out of the
+/// parent while walking, so `parent` maps `key` to an empty compound.
+pub struct WalkContext<'a> {
+    pub parent: &'a JCompound,
+    pub key: &'a JavaStr,
+}
+
+pub trait ContextualMapDataWalker {
+    fn walk(
+        &self,
+        data: &mut JCompound,
+        context: &WalkContext,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    );
+}
+
+pub fn contextual_map_data_walker<'a, F>(func: F) -> impl ContextualMapDataWalker + 'a
+where
+    F: Fn(&mut JCompound, &WalkContext, DataVersion, DataVersion) + 'a,
+{
+    struct ContextualMapDataWalkerImpl<F>(F);
+    impl<F> ContextualMapDataWalker for ContextualMapDataWalkerImpl<F>
+    where
+        F: Fn(&mut JCompound, &WalkContext, DataVersion, DataVersion),
+    {
+        fn walk(
+            &self,
+            data: &mut JCompound,
+            context: &WalkContext,
+            from_version: DataVersion,
+            to_version: DataVersion,
+        ) {
+            (self.0)(data, context, from_version, to_version)
+        }
+    }
+    ContextualMapDataWalkerImpl(func)
+}
+
+impl<T: ContextualMapDataWalker + ?Sized> ContextualMapDataWalker for &T {
+    fn walk(
+        &self,
+        data: &mut JCompound,
+        context: &WalkContext,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) {
+        T::walk(self, data, context, from_version, to_version)
+    }
+}
+
 pub trait DynamicDataWalker {
     fn walk(&self, data: &mut JValue, from_version: DataVersion, to_version: DataVersion);
 }
@@ -0,0 +1,98 @@
+use crate::utils::push_homogeneous;
+use crate::{string_from_java, JCompound, JList, JValue, Result};
+use java_string::JavaString;
+use std::collections::HashMap;
+
+/// hematite-nbt lists may contain mixed tag types, elements which don't match the type of the
+/// first element of a list are dropped.
+pub fn hematite_to_java(compound: HashMap<String, nbt::Value>) -> JCompound {
+    let mut result = JCompound::with_capacity(compound.len());
+    for (key, value) in compound {
+        result.insert(JavaString::from(key), hematite_value_to_java(value));
+    }
+    result
+}
+
+fn hematite_value_to_java(value: nbt::Value) -> JValue {
+    match value {
+        nbt::Value::Byte(v) => JValue::Byte(v),
+        nbt::Value::Short(v) => JValue::Short(v),
+        nbt::Value::Int(v) => JValue::Int(v),
+        nbt::Value::Long(v) => JValue::Long(v),
+        nbt::Value::Float(v) => JValue::Float(v),
+        nbt::Value::Double(v) => JValue::Double(v),
+        nbt::Value::ByteArray(v) => JValue::ByteArray(v),
+        nbt::Value::String(v) => JValue::String(JavaString::from(v)),
+        nbt::Value::List(v) => {
+            let mut result = JList::new();
+            let mut dropped = 0;
+            for (index, value) in v.into_iter().enumerate() {
+                push_homogeneous(
+                    &mut result,
+                    hematite_value_to_java(value),
+                    index,
+                    &mut dropped,
+                );
+            }
+            JValue::List(result)
+        }
+        nbt::Value::Compound(v) => JValue::Compound(hematite_to_java(v)),
+        nbt::Value::IntArray(v) => JValue::IntArray(v),
+        nbt::Value::LongArray(v) => JValue::LongArray(v),
+    }
+}
+
+/// Fails if any string contains unpaired surrogates, which hematite-nbt can't represent. Note that
+/// hematite-nbt lists don't store their element type, so an empty list of any type converts back
+/// as an empty list with no type.
+pub fn java_to_hematite(compound: JCompound) -> Result<HashMap<String, nbt::Value>> {
+    let mut result = HashMap::with_capacity(compound.len());
+    for (key, value) in compound {
+        result.insert(string_from_java(&key)?, java_value_to_hematite(value)?);
+    }
+    Ok(result)
+}
+
+fn java_value_to_hematite(value: JValue) -> Result<nbt::Value> {
+    Ok(match value {
+        JValue::Byte(v) => nbt::Value::Byte(v),
+        JValue::Short(v) => nbt::Value::Short(v),
+        JValue::Int(v) => nbt::Value::Int(v),
+        JValue::Long(v) => nbt::Value::Long(v),
+        JValue::Float(v) => nbt::Value::Float(v),
+        JValue::Double(v) => nbt::Value::Double(v),
+        JValue::ByteArray(v) => nbt::Value::ByteArray(v),
+        JValue::String(v) => nbt::Value::String(string_from_java(&v)?),
+        JValue::List(v) => nbt::Value::List(java_list_to_hematite(v)?),
+        JValue::Compound(v) => nbt::Value::Compound(java_to_hematite(v)?),
+        JValue::IntArray(v) => nbt::Value::IntArray(v),
+        JValue::LongArray(v) => nbt::Value::LongArray(v),
+    })
+}
+
+fn java_list_to_hematite(list: JList) -> Result<Vec<nbt::Value>> {
+    Ok(match list {
+        JList::End => Vec::new(),
+        JList::Byte(v) => v.into_iter().map(nbt::Value::Byte).collect(),
+        JList::Short(v) => v.into_iter().map(nbt::Value::Short).collect(),
+        JList::Int(v) => v.into_iter().map(nbt::Value::Int).collect(),
+        JList::Long(v) => v.into_iter().map(nbt::Value::Long).collect(),
+        JList::Float(v) => v.into_iter().map(nbt::Value::Float).collect(),
+        JList::Double(v) => v.into_iter().map(nbt::Value::Double).collect(),
+        JList::ByteArray(v) => v.into_iter().map(nbt::Value::ByteArray).collect(),
+        JList::String(v) => v
+            .iter()
+            .map(|string| Ok(nbt::Value::String(string_from_java(string)?)))
+            .collect::<Result<_>>()?,
+        JList::List(v) => v
+            .into_iter()
+            .map(|list| Ok(nbt::Value::List(java_list_to_hematite(list)?)))
+            .collect::<Result<_>>()?,
+        JList::Compound(v) => v
+            .into_iter()
+            .map(|compound| Ok(nbt::Value::Compound(java_to_hematite(compound)?)))
+            .collect::<Result<_>>()?,
+        JList::IntArray(v) => v.into_iter().map(nbt::Value::IntArray).collect(),
+        JList::LongArray(v) => v.into_iter().map(nbt::Value::LongArray).collect(),
+    })
+}
@@ -0,0 +1,232 @@
+use crate::utils::push_homogeneous;
+use crate::{nbt_type, AbstractMapDataType, DataVersion, JCompound, JList, JValue, JValueRef};
+use java_string::{JavaStr, JavaString};
+use serde_json::{Map, Number, Value};
+
+/// Converts a value to JSON. This is lossy: numbers lose their tag type, arrays become plain JSON
+/// arrays, non-finite floats become `null`, and unpaired surrogates in strings and keys are
+/// replaced with U+FFFD.
+pub fn jvalue_to_json(value: &JValue) -> Value {
+    value_ref_to_json(value.as_value_ref())
+}
+
+pub fn compound_to_json(compound: &JCompound) -> Value {
+    Value::Object(
+        compound
+            .iter()
+            .map(|(key, value)| {
+                (
+                    key.as_str_lossy().into_owned(),
+                    value_ref_to_json(value.as_value_ref()),
+                )
+            })
+            .collect(),
+    )
+}
+
+fn value_ref_to_json(value: JValueRef) -> Value {
+    match value {
+        JValueRef::Byte(v) => Value::from(*v),
+        JValueRef::Short(v) => Value::from(*v),
+        JValueRef::Int(v) => Value::from(*v),
+        JValueRef::Long(v) => Value::from(*v),
+        JValueRef::Float(v) => float_to_json((*v).into()),
+        JValueRef::Double(v) => float_to_json(*v),
+        JValueRef::ByteArray(v) => v.iter().copied().collect(),
+        JValueRef::String(v) => Value::String(v.as_str_lossy().into_owned()),
+        JValueRef::List(v) => v.iter().map(value_ref_to_json).collect(),
+        JValueRef::Compound(v) => compound_to_json(v),
+        JValueRef::IntArray(v) => v.iter().copied().collect(),
+        JValueRef::LongArray(v) => v.iter().copied().collect(),
+    }
+}
+
+fn float_to_json(v: f64) -> Value {
+    Number::from_f64(v).map_or(Value::Null, Value::Number)
+}
+
+/// Best-effort conversion from JSON. Booleans become bytes, integers become ints or longs if they
+/// don't fit, other numbers become doubles, and arrays become lists. `null` has no equivalent and
+/// gives `None`; nulls in arrays and objects are skipped, as are array elements whose converted
+/// type doesn't match the first element.
+pub fn json_to_jvalue(value: &Value) -> Option<JValue> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(v) => JValue::Byte(i8::from(*v)),
+        Value::Number(v) => match v.as_i64() {
+            Some(v) => match i32::try_from(v) {
+                Ok(v) => JValue::Int(v),
+                Err(_) => JValue::Long(v),
+            },
+            None => JValue::Double(v.as_f64()?),
+        },
+        Value::String(v) => JValue::String(JavaString::from(v.as_str())),
+        Value::Array(v) => {
+            let mut result = JList::new();
+            let mut dropped = 0;
+            for (index, element) in v.iter().filter_map(json_to_jvalue).enumerate() {
+                push_homogeneous(&mut result, element, index, &mut dropped);
+            }
+            JValue::List(result)
+        }
+        Value::Object(v) => JValue::Compound(json_to_compound(v)),
+    })
+}
+
+pub fn json_to_compound(object: &Map<String, Value>) -> JCompound {
+    let mut result = JCompound::with_capacity(object.len());
+    for (key, value) in object {
+        if let Some(value) = json_to_jvalue(value) {
+            result.insert(JavaString::from(key.as_str()), value);
+        }
+    }
+    result
+}
+
+/// Converts the text component at `key`, which may be stringified JSON or a compound, with
+/// `data_type`. A string is parsed into a compound first: JSON objects are used as is, and
+/// anything else, including strings which aren't valid JSON, becomes a plain text component.
+/// Afterwards the component is left as a compound if `to_version` is at or after
+/// `compound_since`, and is stringified again otherwise. A component the converter didn't change
+/// is left as it was if it's already in the right form.
+///
+/// Unlike [`json_to_compound`], this keeps what the component formats need: booleans are stored as
+/// bytes and turned back into booleans for the known boolean style fields, and lists whose
+/// elements have different types have each element wrapped in a compound with an empty key, as
+/// Minecraft does.
+pub fn convert_text_component<T>(
+    data_type: T,
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    from_version: DataVersion,
+    to_version: DataVersion,
+    compound_since: DataVersion,
+) where
+    T: AbstractMapDataType,
+{
+    let Some(value) = map.get_mut(key.as_ref()) else {
+        return;
+    };
+    let (original, was_compound) = match value {
+        JValue::String(str) => (text_component_from_json(str), false),
+        JValue::Compound(component) => (component.clone(), true),
+        _ => return,
+    };
+    let mut component = original.clone();
+    data_type.convert(&mut component, from_version, to_version);
+
+    let as_compound = to_version >= compound_since;
+    if component == original && was_compound == as_compound {
+        return;
+    }
+    *value = if as_compound {
+        JValue::Compound(component)
+    } else {
+        JValue::String(JavaString::from(
+            component_compound_to_json(&component).to_string(),
+        ))
+    };
+}
+
+/// The style fields of a text component which hold booleans.
+const BOOLEAN_COMPONENT_KEYS: [&str; 6] = [
+    "bold",
+    "italic",
+    "underlined",
+    "strikethrough",
+    "obfuscated",
+    "interpret",
+];
+
+fn text_component_from_json(str: &JavaStr) -> JCompound {
+    let json = str
+        .as_str()
+        .ok()
+        .and_then(|str| serde_json::from_str::<Value>(str).ok());
+    match json {
+        Some(Value::Object(object)) => component_compound_from_json(&object),
+        Some(Value::String(text)) => plain_text_component(JavaString::from(text)),
+        Some(Value::Array(elements)) => {
+            let mut component = plain_text_component(JavaString::new());
+            if let Some(extra) = component_value_from_json(&Value::Array(elements)) {
+                component.insert("extra", extra);
+            }
+            component
+        }
+        Some(Value::Null) | None => plain_text_component(str.to_owned()),
+        Some(other) => plain_text_component(JavaString::from(other.to_string())),
+    }
+}
+
+fn plain_text_component(text: JavaString) -> JCompound {
+    let mut component = JCompound::new();
+    component.insert("text", text);
+    component
+}
+
+fn component_compound_from_json(object: &Map<String, Value>) -> JCompound {
+    let mut result = JCompound::with_capacity(object.len());
+    for (key, value) in object {
+        if let Some(value) = component_value_from_json(value) {
+            result.insert(JavaString::from(key.as_str()), value);
+        }
+    }
+    result
+}
+
+fn component_value_from_json(value: &Value) -> Option<JValue> {
+    match value {
+        Value::Array(elements) => {
+            let elements: Vec<JValue> = elements
+                .iter()
+                .filter_map(component_value_from_json)
+                .collect();
+            let homogeneous = elements
+                .windows(2)
+                .all(|pair| nbt_type(&pair[0]) == nbt_type(&pair[1]));
+            let mut result = JList::new();
+            for element in elements {
+                let element = if homogeneous {
+                    element
+                } else {
+                    let mut wrapper = JCompound::new();
+                    wrapper.insert("", element);
+                    JValue::Compound(wrapper)
+                };
+                // the elements all have the same type by now
+                result.try_push(element);
+            }
+            Some(JValue::List(result))
+        }
+        Value::Object(object) => Some(JValue::Compound(component_compound_from_json(object))),
+        _ => json_to_jvalue(value),
+    }
+}
+
+fn component_compound_to_json(compound: &JCompound) -> Value {
+    Value::Object(
+        compound
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    JValue::Byte(v) if BOOLEAN_COMPONENT_KEYS.iter().any(|k| key == *k) => {
+                        Value::Bool(*v != 0)
+                    }
+                    _ => component_value_to_json(value.as_value_ref()),
+                };
+                (key.as_str_lossy().into_owned(), value)
+            })
+            .collect(),
+    )
+}
+
+fn component_value_to_json(value: JValueRef) -> Value {
+    match value {
+        JValueRef::List(list) => list.iter().map(component_value_to_json).collect(),
+        JValueRef::Compound(compound) => match compound.get("") {
+            Some(element) if compound.len() == 1 => component_value_to_json(element.as_value_ref()),
+            _ => component_compound_to_json(compound),
+        },
+        _ => value_ref_to_json(value),
+    }
+}
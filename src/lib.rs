@@ -1,9 +1,33 @@
 mod convert;
+#[cfg(feature = "hematite-nbt")]
+mod hematite;
+#[cfg(feature = "serde_json")]
+mod json;
+#[cfg(feature = "quartz_nbt")]
+mod quartz;
+#[cfg(feature = "region")]
+mod region;
+mod schema;
+mod tag;
+#[cfg(any(test, feature = "test-util"))]
+mod test_util;
 mod utils;
 
 pub use crate::convert::*;
+#[cfg(feature = "hematite-nbt")]
+pub use crate::hematite::*;
+#[cfg(feature = "serde_json")]
+pub use crate::json::*;
+#[cfg(feature = "quartz_nbt")]
+pub use crate::quartz::*;
+#[cfg(feature = "region")]
+pub use crate::region::*;
+pub use crate::schema::*;
+pub use crate::tag::*;
+#[cfg(any(test, feature = "test-util"))]
+pub use crate::test_util::*;
 pub use crate::utils::*;
-use java_string::JavaString;
+use java_string::{JavaStr, JavaString};
 
 pub type JCompound = valence_nbt::Compound<JavaString>;
 pub type JList = valence_nbt::List<JavaString>;
@@ -58,14 +82,99 @@ pub fn compound_to_java(compound: valence_nbt::Compound) -> JCompound {
     result
 }
 
+/// The reverse of [`value_to_java`]. Fails if any string contains unpaired surrogates.
+pub fn value_from_java(value: JValue) -> Result<valence_nbt::Value> {
+    Ok(match value {
+        JValue::Byte(v) => valence_nbt::Value::Byte(v),
+        JValue::Short(v) => valence_nbt::Value::Short(v),
+        JValue::Int(v) => valence_nbt::Value::Int(v),
+        JValue::Long(v) => valence_nbt::Value::Long(v),
+        JValue::Float(v) => valence_nbt::Value::Float(v),
+        JValue::Double(v) => valence_nbt::Value::Double(v),
+        JValue::ByteArray(v) => valence_nbt::Value::ByteArray(v),
+        JValue::String(v) => valence_nbt::Value::String(string_from_java(&v)?),
+        JValue::List(v) => valence_nbt::Value::List(list_from_java(v)?),
+        JValue::Compound(v) => valence_nbt::Value::Compound(compound_from_java(v)?),
+        JValue::IntArray(v) => valence_nbt::Value::IntArray(v),
+        JValue::LongArray(v) => valence_nbt::Value::LongArray(v),
+    })
+}
+
+pub(crate) fn string_from_java(string: &JavaStr) -> Result<String> {
+    match string.as_str() {
+        Ok(string) => Ok(string.to_owned()),
+        Err(_) => Err(ConversionError {
+            message: format!(
+                "String contains unpaired surrogates: {}",
+                string.as_str_lossy()
+            ),
+        }),
+    }
+}
+
+fn list_from_java(list: JList) -> Result<valence_nbt::List> {
+    Ok(match list {
+        JList::End => valence_nbt::List::End,
+        JList::Byte(v) => valence_nbt::List::Byte(v),
+        JList::Short(v) => valence_nbt::List::Short(v),
+        JList::Int(v) => valence_nbt::List::Int(v),
+        JList::Long(v) => valence_nbt::List::Long(v),
+        JList::Float(v) => valence_nbt::List::Float(v),
+        JList::Double(v) => valence_nbt::List::Double(v),
+        JList::ByteArray(v) => valence_nbt::List::ByteArray(v),
+        JList::String(v) => valence_nbt::List::String(
+            v.iter()
+                .map(|string| string_from_java(string))
+                .collect::<Result<_>>()?,
+        ),
+        JList::List(v) => {
+            valence_nbt::List::List(v.into_iter().map(list_from_java).collect::<Result<_>>()?)
+        }
+        JList::Compound(v) => valence_nbt::List::Compound(
+            v.into_iter()
+                .map(compound_from_java)
+                .collect::<Result<_>>()?,
+        ),
+        JList::IntArray(v) => valence_nbt::List::IntArray(v),
+        JList::LongArray(v) => valence_nbt::List::LongArray(v),
+    })
+}
+
+/// The reverse of [`compound_to_java`]. Fails if any string contains unpaired surrogates.
+pub fn compound_from_java(compound: JCompound) -> Result<valence_nbt::Compound> {
+    let mut result = valence_nbt::Compound::with_capacity(compound.len());
+    for (key, value) in compound {
+        result.insert(string_from_java(&key)?, value_from_java(value)?);
+    }
+    Ok(result)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        convert_map_in_map, convert_object_in_map, map_data_converter_func, map_data_walker,
-        value_data_converter_func, value_to_java, AbstractMapDataType, IdDataType, JCompound,
-        JValue, MapDataType, ObjectDataType,
+        ascii_lowercase_keys, assert_compound_eq, byte_array_len, compound_entries_of_type_mut,
+        compound_from_java, contextual_map_data_walker, convert_dynamic_list_in_map,
+        convert_dynamic_list_in_map_buffered, convert_dynamic_list_in_map_with_policy,
+        convert_map_in_map, convert_map_in_map_dynamic, convert_map_in_nested_lists,
+        convert_map_list_in_map, convert_object_in_map, convert_object_list_in_map,
+        convert_object_or_list_in_map, convert_palette_and_indices, convert_slotted_list_in_map,
+        convert_split_list_in_map, dedup_list_in_map, dynamic_data_converter_func,
+        ensure_field_typed, flat_map_list_in_map, int_array_len, list_element_type, list_len,
+        long_array_len, map_data_converter_func, map_data_walker, narrow_to_byte, narrow_to_int,
+        remap_string_value, remap_string_values_in_list, remove_run_once_markers, run_once_at,
+        sequence_all, sort_list_in_map, swap_int_array_endianness, swap_long_array_endianness,
+        try_map_data_walker, value_data_converter_func, value_to_java, AbstractMapDataType,
+        BitStorageLayout, ContextualMapDataType, ConversionError, ConversionObserver, DataVersion,
+        DataVersionRange, DataWalkerKeyRenamer, DynamicDataType, FieldAction, IdDataType,
+        IdFilterScope, JCompound, JList, JValue, LazyConverted, ListConversionPolicy,
+        MapDataConverterFunc, MapDataHook, MapDataType, MapDataWalker, NarrowingPolicy, NbtType,
+        NumberKind, NumericIdDataType, ObjectDataType, PlanStep, PlanStepKind, ResourceLocation,
+        Schema, SchemaError, SchemaErrorKind, SumTypeDataType, TypeRegistry,
     };
-    use java_string::JavaString;
+    use java_string::{JavaStr, JavaString};
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::rc::Rc;
 
     fn make_map(string: &str) -> JCompound {
         let value =
@@ -84,6 +193,275 @@ mod tests {
         assert!(!map.contains_key("hello"));
     }
 
+    #[test]
+    fn rename_key_if() {
+        let mut map = make_map(r#"{"Color": 3b, "color": "red"}"#);
+        let is_dye = |value: &JValue| matches!(value, JValue::Byte(0..=15));
+        assert!(crate::rename_key_if(&mut map, "Color", "DyeColor", is_dye));
+        assert!(!crate::rename_key_if(
+            &mut map,
+            "color",
+            "DyeColor2",
+            is_dye
+        ));
+        assert!(!crate::rename_key_if(
+            &mut map,
+            "missing",
+            "DyeColor3",
+            is_dye
+        ));
+        assert_compound_eq(&make_map(r#"{"DyeColor": 3b, "color": "red"}"#), &map);
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn rename_key_to_itself() {
+        let mut map = JCompound::new();
+        for key in ["x", "y", "z"] {
+            map.insert(key, 1);
+        }
+        crate::rename_key(&mut map, "x", "x");
+        assert_eq!(
+            vec!["x", "y", "z"],
+            map.keys()
+                .map(|key| key.as_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn resource_location() {
+        let parse =
+            |id: &str| ResourceLocation::parse(JavaStr::from_str(id)).map(|id| id.to_java_string());
+        assert_eq!(Some(JavaString::from("minecraft:stone")), parse("stone"));
+        assert_eq!(Some(JavaString::from("minecraft:stone")), parse(":stone"));
+        assert_eq!(Some(JavaString::from("mod:a/b")), parse("mod:a/b"));
+        assert_eq!(Some(JavaString::from("minecraft:")), parse(""));
+        assert_eq!(None, parse("Stone"));
+        assert_eq!(None, parse("mod/x:stone"));
+
+        let mut map = make_map(r#"{"id": "stone", "other": "dirt", "bad": "A B"}"#);
+        crate::namespace_ids_in_map(&mut map, &["id", "bad", "missing"]);
+        assert!(matches!(map.get("id"), Some(JValue::String(id)) if id == "minecraft:stone"));
+        assert!(matches!(map.get("other"), Some(JValue::String(id)) if id == "dirt"));
+        assert!(matches!(map.get("bad"), Some(JValue::String(id)) if id == "A B"));
+    }
+
+    #[test]
+    fn wrap_in_list() {
+        let mut map =
+            make_map(r#"{"byte": 1b, "string": "foo", "compound": {"a": 1}, "list": [1, 2]}"#);
+        for key in ["byte", "string", "compound", "list", "missing"] {
+            crate::wrap_in_list(&mut map, key);
+        }
+        assert!(matches!(map.get("byte"), Some(JValue::List(JList::Byte(v))) if v == &[1]));
+        assert!(
+            matches!(map.get("string"), Some(JValue::List(JList::String(v))) if v.len() == 1 && &v[0] == "foo")
+        );
+        assert!(
+            matches!(map.get("compound"), Some(JValue::List(JList::Compound(v))) if v.len() == 1 && v[0].contains_key("a"))
+        );
+        assert!(matches!(map.get("list"), Some(JValue::List(JList::Int(v))) if v == &[1, 2]));
+        assert!(!map.contains_key("missing"));
+    }
+
+    #[test]
+    fn unwrap_single_list() {
+        let mut map = make_map(
+            r#"{"single": [{"a": 1}], "multi": [1, 2], "empty1": [], "empty2": [], "scalar": 1}"#,
+        );
+        for key in ["single", "multi", "empty1", "scalar", "missing"] {
+            crate::unwrap_single_list(&mut map, key, false);
+        }
+        crate::unwrap_single_list(&mut map, "empty2", true);
+        assert!(matches!(map.get("single"), Some(JValue::Compound(c)) if c.contains_key("a")));
+        assert!(matches!(map.get("multi"), Some(JValue::List(JList::Int(v))) if v == &[1, 2]));
+        assert!(matches!(map.get("empty1"), Some(JValue::List(list)) if list.is_empty()));
+        assert!(!map.contains_key("empty2"));
+        assert!(matches!(map.get("scalar"), Some(JValue::Int(1))));
+    }
+
+    #[test]
+    fn move_up() {
+        let mut map = make_map(r#"{"tag": {"Damage": 3, "Other": 1}, "item": {"Count": 2}}"#);
+        crate::move_up(&mut map, "tag", "Damage", "Damage", true);
+        crate::move_up(&mut map, "item", "Count", "Count", true);
+        crate::move_up(&mut map, "missing", "Count", "Missing", true);
+        assert!(matches!(map.get("Damage"), Some(JValue::Int(3))));
+        assert!(matches!(map.get("tag"), Some(JValue::Compound(tag)) if tag.len() == 1));
+        assert!(matches!(map.get("Count"), Some(JValue::Int(2))));
+        assert!(!map.contains_key("item"));
+        assert!(!map.contains_key("Missing"));
+    }
+
+    #[test]
+    fn move_down() {
+        let mut map =
+            make_map(r#"{"Damage": 3, "Count": 2, "Name": "a", "components": {}, "id": 1}"#);
+        crate::move_down(&mut map, "Damage", "components");
+        crate::move_down(&mut map, "Count", "new");
+        crate::move_down(&mut map, "Name", "id");
+        crate::move_down(&mut map, "Missing", "components");
+        assert!(
+            matches!(map.get("components"), Some(JValue::Compound(components)) if components.len() == 1 && matches!(components.get("Damage"), Some(JValue::Int(3))))
+        );
+        assert!(
+            matches!(map.get("new"), Some(JValue::Compound(new)) if matches!(new.get("Count"), Some(JValue::Int(2))))
+        );
+        assert!(map.contains_key("Name"));
+        assert!(!map.contains_key("Damage"));
+    }
+
+    #[test]
+    fn convert_if_field_below() {
+        let mut map = make_map(r#"{"old": {"v": 1}, "new": {"v": 5}, "missing": {}}"#);
+        for key in ["old", "new", "missing"] {
+            if let Some(JValue::Compound(child)) = map.get_mut(key) {
+                crate::convert_if_field_below(child, "v", 3, |child| {
+                    child.insert("converted", JValue::Byte(1));
+                });
+            }
+        }
+        for (key, converted, version) in [("old", true, 3), ("new", false, 5), ("missing", true, 3)]
+        {
+            let Some(JValue::Compound(child)) = map.get(key) else {
+                panic!("{key} was not a compound");
+            };
+            assert_eq!(converted, child.contains_key("converted"));
+            assert!(matches!(child.get("v"), Some(JValue::Int(v)) if *v == version));
+        }
+    }
+
+    #[test]
+    fn remap_string_values() {
+        let table = HashMap::from([(JavaString::from("up"), JavaString::from("upward"))]);
+        let mut map =
+            make_map(r#"{"facing": "up", "other": "down", "all": ["up", "down"], "num": 1}"#);
+        remap_string_value(&mut map, "facing", &table);
+        remap_string_value(&mut map, "other", &table);
+        remap_string_value(&mut map, "num", &table);
+        remap_string_values_in_list(&mut map, "all", &table);
+        assert!(matches!(map.get("facing"), Some(JValue::String(str)) if str == "upward"));
+        assert!(matches!(map.get("other"), Some(JValue::String(str)) if str == "down"));
+        assert!(matches!(map.get("num"), Some(JValue::Int(1))));
+        assert!(
+            matches!(map.get("all"), Some(JValue::List(JList::String(strs))) if strs[0] == "upward" && strs[1] == "down")
+        );
+    }
+
+    #[test]
+    fn unwrap_compound() {
+        let mut map = make_map(
+            r#"{"single": {"value": 1}, "multi": {"value": 2, "other": 3}, "wrong": {"other": 4}}"#,
+        );
+        crate::unwrap_compound(&mut map, "single", "value");
+        crate::unwrap_compound(&mut map, "multi", "value");
+        crate::unwrap_compound(&mut map, "wrong", "value");
+        assert!(matches!(map.get("single"), Some(JValue::Int(1))));
+        assert!(matches!(map.get("multi"), Some(JValue::Compound(multi)) if multi.len() == 2));
+        assert!(matches!(map.get("wrong"), Some(JValue::Compound(wrong)) if wrong.len() == 1));
+    }
+
+    #[test]
+    fn wrap_in_compound() {
+        let mut map = make_map(r#"{"test": 1}"#);
+        crate::wrap_in_compound(&mut map, "test", "value");
+        crate::wrap_in_compound(&mut map, "missing", "value");
+        assert!(
+            matches!(map.get("test"), Some(JValue::Compound(test)) if matches!(test.get("value"), Some(JValue::Int(1))))
+        );
+        assert!(!map.contains_key("missing"));
+    }
+
+    #[test]
+    fn compound_to_list() {
+        let mut map = make_map(
+            r#"{"list": {"1": "b", "0": "a"}, "gap": {"0": 1, "2": 2}, "mixed": {"0": 1, "1": "a"}}"#,
+        );
+        crate::compound_to_list(&mut map, "list");
+        crate::compound_to_list(&mut map, "gap");
+        crate::compound_to_list(&mut map, "mixed");
+        assert!(
+            matches!(map.get("list"), Some(JValue::List(JList::String(strs))) if strs.len() == 2 && strs[0] == "a" && strs[1] == "b")
+        );
+        assert!(matches!(map.get("gap"), Some(JValue::Compound(_))));
+        assert!(matches!(map.get("mixed"), Some(JValue::Compound(_))));
+
+        crate::list_to_compound(&mut map, "list");
+        assert!(
+            matches!(map.get("list"), Some(JValue::Compound(compound)) if matches!(compound.get("1"), Some(JValue::String(str)) if str == "b"))
+        );
+    }
+
+    #[test]
+    fn pack_flags() {
+        const FLAGS: [(&str, u32); 3] = [("a", 0), ("b", 1), ("c", 5)];
+        for packed in 0..8 {
+            let mut map = JCompound::new();
+            for (i, (key, _)) in FLAGS.iter().enumerate() {
+                if packed & (1 << i) != 0 {
+                    map.insert(*key, JValue::Byte(1));
+                } else if i == 0 {
+                    // missing flags count as 0
+                    map.insert(*key, JValue::Byte(0));
+                }
+            }
+            crate::pack_flags(&mut map, &FLAGS, "flags");
+            let expected = (packed & 3) | ((packed & 4) << 3);
+            assert!(matches!(map.get("flags"), Some(JValue::Int(flags)) if *flags == expected));
+            assert_eq!(1, map.len());
+
+            crate::unpack_flags(&mut map, "flags", &FLAGS);
+            assert!(!map.contains_key("flags"));
+            for (i, (key, _)) in FLAGS.iter().enumerate() {
+                let expected = ((packed >> i) & 1) as i8;
+                assert!(matches!(map.get(*key), Some(JValue::Byte(flag)) if *flag == expected));
+            }
+        }
+
+        let mut map = make_map(r#"{"a": 1b, "far": 1b}"#);
+        crate::pack_flags(&mut map, &[("a", 31), ("far", 32)], "flags");
+        assert_eq!(make_map(r#"{"far": 1b, "flags": -2147483648}"#), map);
+        map.remove("far");
+        crate::unpack_flags(&mut map, "flags", &[("a", 31), ("far", 32)]);
+        assert_eq!(make_map(r#"{"a": 1b}"#), map);
+    }
+
+    #[test]
+    fn semantically_eq() {
+        let a = JValue::Compound(make_map(
+            r#"{"a": 1b, "b": [1s, 2s], "c": {"d": 1.5f}, "e": [B; 1b, 2b], "f": "x"}"#,
+        ));
+        let b = JValue::Compound(make_map(
+            r#"{"a": 1.0d, "b": [1L, 2L], "c": {"d": 1.5d}, "e": [I; 1, 2], "f": "x"}"#,
+        ));
+        let c = JValue::Compound(make_map(
+            r#"{"a": 1b, "b": [1s, 2s], "c": {"d": 1.5f}, "e": [B; 1b, 2b], "f": "1"}"#,
+        ));
+        assert!(crate::semantically_eq(&a, &b));
+        assert!(!crate::semantically_eq(&a, &c));
+        assert!(!crate::semantically_eq(
+            &JValue::Int(1),
+            &JValue::String(JavaString::from("1"))
+        ));
+    }
+
+    #[test]
+    fn string_to_number() {
+        let mut map =
+            make_map(r#"{"byte": "1b", "int": " 42 ", "float": "2.0f", "bad": "abc", "num": 1}"#);
+        crate::string_to_number(&mut map, "byte", NumberKind::Byte);
+        crate::string_to_number(&mut map, "int", NumberKind::Int);
+        crate::string_to_number(&mut map, "float", NumberKind::Float);
+        crate::string_to_number(&mut map, "bad", NumberKind::Int);
+        crate::string_to_number(&mut map, "num", NumberKind::Long);
+        assert!(matches!(map.get("byte"), Some(JValue::Byte(1))));
+        assert!(matches!(map.get("int"), Some(JValue::Int(42))));
+        assert!(matches!(map.get("float"), Some(JValue::Float(f)) if *f == 2.0));
+        assert!(matches!(map.get("bad"), Some(JValue::String(str)) if str == "abc"));
+        assert!(matches!(map.get("num"), Some(JValue::Int(1))));
+    }
+
     #[test]
     fn simple_conversion() {
         let mut map = make_map(r#"{"test": 42}"#);
@@ -92,48 +470,2162 @@ mod tests {
     }
 
     #[test]
-    fn simple_walker() {
-        let mut map = make_map(r#"{"inner": {"test": 42}}"#);
-        let mut typ = MapDataType::new("Outer");
-        let inner_type = simple_converted_type();
+    fn single_converter() {
+        let mut map = make_map(r#"{"test": 42}"#);
+        let mut typ = simple_converted_type();
+        typ.add_structure_converter(
+            2,
+            map_data_converter_func(|data, from_version, to_version| {
+                assert_eq!(1, from_version.get_version());
+                assert_eq!(2, to_version.get_version());
+                data.insert("second", JValue::Byte(1));
+            }),
+        );
+        assert!(typ.run_single_converter(2, &mut map));
+        assert!(matches!(map.get("test"), Some(JValue::Int(42))));
+        assert!(map.contains_key("second"));
+        assert!(!typ.run_single_converter(3, &mut map));
+    }
+
+    #[test]
+    fn same_version_converters_keep_registration_order() {
+        let mut map = make_map(r#"{"order": ""}"#);
+        let mut typ = MapDataType::new("Test");
+        for (version, c) in [(2, 'b'), (2, 'c'), (1, 'a'), (2, 'd')] {
+            typ.add_structure_converter(
+                version,
+                map_data_converter_func(move |data, _from_version, _to_version| {
+                    if let Some(JValue::String(order)) = data.get_mut("order") {
+                        order.push(c);
+                    }
+                }),
+            );
+        }
+        typ.convert(&mut map, 0.into(), 2.into());
+        assert!(matches!(map.get("order"), Some(JValue::String(order)) if order == "abcd"));
+    }
+
+    #[test]
+    fn data_version_arithmetic() {
+        assert_eq!(DataVersion::new(4, 0), DataVersion::new(5, 2).previous());
+        assert_eq!(DataVersion::new(0, 0), DataVersion::new(0, 1).previous());
+        assert_eq!(DataVersion::new(6, 0), DataVersion::new(5, 2).next());
+        assert_eq!(
+            DataVersion::new(u32::MAX, 0),
+            DataVersion::new(u32::MAX, 0).next()
+        );
+        assert_eq!(DataVersion::new(5, 3), DataVersion::new(5, 2).with_step(3));
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(DataVersion::from(0), DataVersion::default());
+        let mut typ = simple_converted_type();
+        assert_eq!("Test", std::mem::take(&mut typ).name);
+        assert_eq!("", typ.name);
+    }
+
+    #[test]
+    fn extend_from_keeps_insertion_order() {
+        fn append(c: char) -> Box<dyn MapDataConverterFunc> {
+            Box::new(map_data_converter_func(
+                move |data, _from_version, _to_version| {
+                    if let Some(JValue::String(order)) = data.get_mut("order") {
+                        order.push(c);
+                    }
+                },
+            ))
+        }
+
+        let mut map = make_map(r#"{"order": ""}"#);
+        let mut typ = MapDataType::new("Test");
+        typ.add_structure_converter(2, append('a'));
+        typ.extend_from([(3, append('d')), (2, append('b')), (2, append('c'))]);
+        typ.convert(&mut map, 1.into(), 3.into());
+        assert!(matches!(map.get("order"), Some(JValue::String(order)) if order == "abcd"));
+    }
+
+    #[test]
+    fn describe_plan() {
+        let mut typ = simple_converted_type();
+        typ.add_named_structure_converter(
+            3,
+            "noop",
+            map_data_converter_func(|_data, _from, _to| {}),
+        );
+        typ.add_structure_walker(2, map_data_walker(|_data, _from, _to| {}));
+        let plan = typ.describe_plan(1.into(), 3.into());
+        assert_eq!(
+            vec![
+                PlanStep {
+                    kind: PlanStepKind::Converter,
+                    version: 3.into(),
+                    label: Some("noop".into()),
+                },
+                PlanStep {
+                    kind: PlanStepKind::Walker,
+                    version: 2.into(),
+                    label: None,
+                },
+            ],
+            plan
+        );
+        assert_eq!("converter 3 (noop)", plan[0].to_string());
+    }
+
+    #[test]
+    fn walker_since() {
+        let mut typ = MapDataType::new("Test");
+        typ.add_structure_walker_since(
+            2,
+            map_data_walker(|data, _from_version, _to_version| {
+                data.insert("walked", JValue::Byte(1));
+            }),
+        );
+        let mut map = JCompound::new();
+        typ.convert(&mut map, 2.into(), 3.into());
+        assert!(!map.contains_key("walked"));
+        typ.convert(&mut map, 1.into(), 3.into());
+        assert!(map.contains_key("walked"));
+    }
+
+    #[test]
+    fn observer() {
+        #[derive(Default)]
+        struct Counter {
+            converters: Cell<u32>,
+            walkers: Cell<u32>,
+        }
+        impl ConversionObserver for Counter {
+            fn on_converter(&self, _version: DataVersion) {
+                self.converters.set(self.converters.get() + 1);
+            }
+
+            fn on_walker(&self, _version: DataVersion) {
+                self.walkers.set(self.walkers.get() + 1);
+            }
+        }
+
+        let counter = Counter::default();
+        let mut typ = simple_converted_type().with_observer(&counter);
+        typ.add_structure_walker(1, map_data_walker(|_data, _from, _to| {}));
+        typ.convert(&mut make_map(r#"{"test": 1}"#), 0.into(), 1.into());
+        assert_eq!(1, counter.converters.get());
+        assert_eq!(1, counter.walkers.get());
+    }
+
+    #[test]
+    fn step_iter() {
+        let mut map = make_map(r#"{"test": 42}"#);
+        let mut typ = simple_converted_type();
+        typ.add_structure_converter(3, map_data_converter_func(|_data, _from, _to| {}));
         typ.add_structure_walker(
             1,
-            map_data_walker(move |data, from_version, to_version| {
-                convert_map_in_map(&inner_type, data, "inner", from_version, to_version)
+            map_data_walker(|data, _from_version, _to_version| {
+                data.insert("walked", JValue::Byte(1));
+            }),
+        );
+        let mut steps = typ.step_iter(&mut map, 0.into(), 4.into());
+        assert_eq!(Some(DataVersion::from(1)), steps.next());
+        assert!(matches!(steps.data().get("test"), Some(JValue::String(_))));
+        assert!(!steps.data().contains_key("walked"));
+        assert_eq!(Some(DataVersion::from(3)), steps.next());
+        assert_eq!(Some(DataVersion::from(4)), steps.next());
+        assert_eq!(None, steps.next());
+        assert!(map.contains_key("walked"));
+    }
+
+    #[test]
+    fn converter_windows() {
+        let mut typ = simple_converted_type();
+        typ.add_structure_converter(3, map_data_converter_func(|_data, _from, _to| {}));
+        typ.add_named_structure_converter(
+            3,
+            "second",
+            map_data_converter_func(|_data, _from, _to| {}),
+        );
+        assert_eq!(
+            vec![
+                (DataVersionRange::new(0, 1), None),
+                (DataVersionRange::new(1, 3), None),
+                (DataVersionRange::new(1, 3), Some(Rc::from("second")))
+            ],
+            typ.converter_windows()
+        );
+    }
+
+    #[test]
+    fn validate_versions() {
+        let mut typ = simple_converted_type();
+        typ.add_structure_converter(35780, map_data_converter_func(|_data, _from, _to| {}));
+        assert_eq!(
+            Ok(()),
+            typ.validate_versions(DataVersionRange::new(1, 35781))
+        );
+        assert_eq!(
+            Err(vec![DataVersion::from(1), DataVersion::from(35780)]),
+            typ.validate_versions(DataVersionRange::new(2, 4000))
+        );
+    }
+
+    #[test]
+    fn convert_self_versioned() {
+        let mut typ = MapDataType::new("Test");
+        typ.add_structure_converter(
+            2,
+            map_data_converter_func(|data, _from, _to| {
+                data.insert("converted", true);
+            }),
+        );
+
+        let mut map = make_map(r#"{"Version": 1}"#);
+        typ.convert_self_versioned_with_key(&mut map, "Version", 3.into());
+        assert_eq!(make_map(r#"{"Version": 3, "converted": true}"#), map);
+
+        let mut map = make_map(r#"{"DataVersion": 1}"#);
+        typ.convert_self_versioned(&mut map, 3.into());
+        assert_eq!(make_map(r#"{"DataVersion": 3, "converted": true}"#), map);
+
+        for data in [r#"{}"#, r#"{"Version": -1}"#, r#"{"Version": "1"}"#] {
+            let mut map = make_map(data);
+            typ.convert_self_versioned_with_key(&mut map, "Version", 3.into());
+            assert_eq!(make_map(data), map);
+        }
+
+        let mut map = make_map(r#"{"Version": 1}"#);
+        typ.convert_self_versioned_with_key(&mut map, "Version", DataVersion::new(3, 1));
+        assert_eq!(make_map(r#"{"Version": 1}"#), map);
+    }
+
+    #[test]
+    fn walk_map_in_map_with_context() {
+        let mut map = make_map(r#"{"id": "zombie", "item": {"count": 1}, "after": 1}"#);
+        crate::walk_map_in_map_with_context(
+            contextual_map_data_walker(|data, context, _from, _to| {
+                assert_eq!(context.key, "item");
+                if let Some(JValue::String(id)) = context.parent.get("id") {
+                    data.insert("owner", id.clone());
+                }
             }),
+            &mut map,
+            "item",
+            1.into(),
+            2.into(),
         );
+        assert_eq!(
+            make_map(r#"{"id": "zombie", "item": {"count": 1, "owner": "zombie"}, "after": 1}"#),
+            map
+        );
+        #[cfg(feature = "preserve_order")]
+        assert_eq!(
+            vec!["id", "item", "after"],
+            map.keys()
+                .map(|key| key.as_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn walker_for_range() {
+        fn tagging_walker(tag: &'static str) -> impl MapDataWalker {
+            map_data_walker(move |data, _from, _to| {
+                data.insert(tag, true);
+            })
+        }
+
+        let mut typ = MapDataType::new("Test");
+        typ.add_structure_walker(1, tagging_walker("before"));
+        typ.add_structure_walker(3, tagging_walker("inside"));
+        typ.add_walker_for_range(DataVersionRange::new(2, 5), tagging_walker("range"));
+
+        let walked = |to_version: u32| {
+            let mut map = JCompound::new();
+            typ.convert(&mut map, 0.into(), to_version.into());
+            let mut keys: Vec<_> = map.keys().map(|key| key.as_str().unwrap()).collect();
+            keys.sort_unstable();
+            keys.into_iter().map(str::to_owned).collect::<Vec<_>>()
+        };
+        assert_eq!(vec!["before"], walked(1));
+        assert_eq!(vec!["range"], walked(2));
+        assert_eq!(vec!["inside", "range"], walked(3));
+        assert_eq!(vec!["inside", "range"], walked(4));
+        assert_eq!(vec!["inside"], walked(5));
+        assert_eq!(vec!["inside"], walked(6));
+    }
+
+    #[test]
+    fn check_monotonic() {
+        let mut typ = MapDataType::new("Test");
+        typ.add_structure_converter(2, map_data_converter_func(|_data, _from, _to| {}));
+        typ.add_structure_walker(1, map_data_walker(|_data, _from, _to| {}));
+        typ.add_walker_for_range(
+            DataVersionRange::new(2, 3),
+            map_data_walker(|_data, _from, _to| {}),
+        );
+        assert_eq!(Ok(()), typ.check_monotonic());
+
+        struct NoopHook;
+        impl MapDataHook for NoopHook {
+            fn pre_hook(&self, _data: &mut JCompound, _from: DataVersion, _to: DataVersion) {}
+
+            fn post_hook(&self, _data: &mut JCompound, _from: DataVersion, _to: DataVersion) {}
+        }
+        typ.add_structure_hook(4, NoopHook);
+        assert_eq!(
+            Err("hooks registered at DataVersion { version: 4, step: 0 }, after the last converter at DataVersion { version: 2, step: 0 }".to_owned()),
+            typ.check_monotonic()
+        );
+
+        let mut typ = MapDataType::new("Test");
+        typ.add_walker_for_range(
+            DataVersionRange::new(1, 3),
+            map_data_walker(|_data, _from, _to| {}),
+        );
+        assert_eq!(
+            Err("no walkers apply from DataVersion { version: 3, step: 0 }, the end of a walker range".to_owned()),
+            typ.check_monotonic()
+        );
+        typ.add_structure_walker(3, map_data_walker(|_data, _from, _to| {}));
+        assert_eq!(Ok(()), typ.check_monotonic());
+
+        typ.add_structure_walker(2, map_data_walker(|_data, _from, _to| {}));
+        assert_eq!(
+            Err("walkers registered at DataVersion { version: 2, step: 0 } stop applying at DataVersion { version: 3, step: 0 }, the end of a walker range added before them".to_owned()),
+            typ.check_monotonic()
+        );
+    }
+
+    #[test]
+    fn key_renamer_walker() {
+        let mut typ = MapDataType::new("Test");
+        typ.add_structure_walker(
+            1,
+            DataWalkerKeyRenamer::new(
+                2,
+                |key: &JavaStr| (key == "speed").then(|| JavaString::from("minecraft:speed")),
+                "attributes",
+            ),
+        );
+        let mut map = make_map(r#"{"attributes": {"speed": 1}}"#);
         typ.convert(&mut map, 0.into(), 1.into());
+        typ.convert(&mut map, 2.into(), 3.into());
         assert!(
-            matches!(map.get("inner"), Some(valence_nbt::Value::Compound(inner)) if matches!(inner.get("test"), Some(valence_nbt::Value::String(str)) if str == "42"))
+            matches!(map.get("attributes"), Some(JValue::Compound(attributes)) if attributes.contains_key("speed"))
+        );
+        typ.convert(&mut map, 1.into(), 2.into());
+        assert!(
+            matches!(map.get("attributes"), Some(JValue::Compound(attributes)) if attributes.contains_key("minecraft:speed"))
         );
     }
 
     #[test]
-    fn simple_id_walker() {
-        let mut map1 = make_map(r#"{"id": "foo", "test": 42}"#);
-        let mut map2 = make_map(r#"{"id": "bar", "test": 42}"#);
-        let mut inner_type = ObjectDataType::new("Inner");
-        inner_type.add_structure_converter(
+    fn try_walker_error() {
+        let mut typ = MapDataType::new("Test");
+        typ.add_try_structure_walker(
             1,
-            value_data_converter_func(|data, _from_version, _to_version| {
-                if let valence_nbt::value::ValueMut::Int(i) = data {
-                    **i = 69;
+            try_map_data_walker(|_data, _from_version, _to_version| {
+                Err(ConversionError {
+                    message: "bad data".to_owned(),
+                })
+            }),
+        );
+        typ.add_try_structure_walker(
+            1,
+            try_map_data_walker(|data, _from_version, _to_version| {
+                data.insert("walked", JValue::Byte(1));
+                Ok(())
+            }),
+        );
+        struct PostHook;
+        impl MapDataHook for PostHook {
+            fn pre_hook(&self, _data: &mut JCompound, _from: DataVersion, _to: DataVersion) {}
+
+            fn post_hook(&self, data: &mut JCompound, _from: DataVersion, _to: DataVersion) {
+                data.insert("post_hook", JValue::Byte(1));
+            }
+        }
+        typ.add_structure_hook(1, PostHook);
+        let mut map = JCompound::new();
+        let err = typ.try_convert(&mut map, 0.into(), 1.into()).unwrap_err();
+        assert_eq!("bad data", err.message);
+        assert!(!map.contains_key("walked"));
+        assert!(map.contains_key("post_hook"));
+    }
+
+    #[test]
+    fn per_converter_hook() {
+        struct Log(&'static str);
+        impl MapDataHook for Log {
+            fn pre_hook(&self, data: &mut JCompound, _from: DataVersion, _to: DataVersion) {
+                if let Some(JValue::String(log)) = data.get_mut("log") {
+                    log.push_str(self.0);
                 }
+            }
+
+            fn post_hook(&self, data: &mut JCompound, _from: DataVersion, _to: DataVersion) {
+                if let Some(JValue::String(log)) = data.get_mut("log") {
+                    log.push_str(&self.0.to_uppercase());
+                }
+            }
+        }
+
+        let mut typ = MapDataType::new("Test");
+        typ.add_structure_converter(1, map_data_converter_func(|_data, _from, _to| {}));
+        typ.add_structure_converter(5, map_data_converter_func(|_data, _from, _to| {}));
+        typ.add_per_converter_hook(Log("p"));
+        typ.add_structure_hook(5, Log("s"));
+        let mut map = make_map(r#"{"log": ""}"#);
+        typ.convert(&mut map, 0.into(), 5.into());
+        // the structure hook at 5 is selected for the post hooks of both converters, and runs
+        // around the walkers too
+        assert!(matches!(map.get("log"), Some(JValue::String(log)) if log == "pSPpsSPsS"));
+    }
+
+    #[test]
+    fn pre_hook_adjust() {
+        struct FixVersion;
+        impl MapDataHook for FixVersion {
+            fn pre_hook(&self, _data: &mut JCompound, _from: DataVersion, _to: DataVersion) {}
+
+            fn post_hook(&self, _data: &mut JCompound, _from: DataVersion, _to: DataVersion) {}
+
+            fn pre_hook_adjust(
+                &self,
+                data: &mut JCompound,
+                _from: DataVersion,
+                _to: DataVersion,
+            ) -> Option<DataVersion> {
+                data.remove("actual_version")
+                    .and_then(|version| version.as_i64())
+                    .map(|version| DataVersion::new(version as u32, 0))
+            }
+        }
+
+        let mut typ = MapDataType::new("Test");
+        for version in [1, 2, 3] {
+            typ.add_structure_converter(
+                version,
+                map_data_converter_func(move |data, from_version, _to| {
+                    if let Some(JValue::String(log)) = data.get_mut("log") {
+                        log.push_str(&format!("{version}:{} ", from_version.get_version()));
+                    }
+                }),
+            );
+        }
+        typ.add_per_converter_hook(FixVersion);
+
+        let mut map = make_map(r#"{"log": "", "actual_version": 2}"#);
+        typ.convert(&mut map, 0.into(), 3.into());
+        assert_eq!(make_map(r#"{"log": "3:2 "}"#), map);
+    }
+
+    #[test]
+    fn fingerprint() {
+        let mut typ = simple_converted_type();
+        let fingerprint = typ.fingerprint();
+        assert_eq!(fingerprint, simple_converted_type().fingerprint());
+        assert!(typ.verify_fingerprint(fingerprint));
+
+        typ.add_structure_walker(1, map_data_walker(|_data, _from, _to| {}));
+        assert!(!typ.verify_fingerprint(fingerprint));
+
+        let registrations: [fn(&mut MapDataType); 3] = [
+            |typ| typ.add_terminal_converter(1, map_data_converter_func(|_data, _from, _to| {})),
+            |typ| typ.add_downgrade_converter(1, map_data_converter_func(|_data, _from, _to| {})),
+            |typ| typ.add_schema(1, Schema::builder().build()),
+        ];
+        let mut fingerprints = vec![fingerprint];
+        for register in registrations {
+            let mut typ = simple_converted_type();
+            register(&mut typ);
+            fingerprints.push(typ.fingerprint());
+        }
+        let mut distinct = fingerprints.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert_eq!(fingerprints.len(), distinct.len());
+    }
+
+    #[test]
+    fn terminal_converter() {
+        let mut typ = MapDataType::new("Test");
+        typ.add_terminal_converter(
+            2,
+            map_data_converter_func(|data, _from_version, _to_version| {
+                let walked = data.contains_key("walked");
+                data.insert("terminal_saw_walked", walked);
             }),
         );
-        let mut typ = IdDataType::new("Test");
-        typ.add_walker_for_id(
+        typ.add_structure_walker(
             1,
-            "foo",
-            map_data_walker(move |data, from_version, to_version| {
-                convert_object_in_map(&inner_type, data, "test", from_version, to_version);
+            map_data_walker(|data, _from_version, _to_version| {
+                data.insert("walked", true);
             }),
         );
 
-        typ.convert(&mut map1, 0.into(), 1.into());
-        typ.convert(&mut map2, 0.into(), 1.into());
-        assert_eq!(69, map1.get("test").unwrap().as_i64().unwrap());
-        assert_eq!(42, map2.get("test").unwrap().as_i64().unwrap());
+        let mut map = JCompound::new();
+        typ.convert(&mut map, 0.into(), 1.into());
+        assert!(!map.contains_key("terminal_saw_walked"));
+        typ.convert(&mut map, 2.into(), 5.into());
+        assert!(!map.contains_key("terminal_saw_walked"));
+        typ.convert(&mut map, 0.into(), 5.into());
+        assert_eq!(Some(&JValue::Byte(1)), map.get("terminal_saw_walked"));
+
+        assert_eq!(
+            vec![
+                PlanStep {
+                    kind: PlanStepKind::Walker,
+                    version: 1.into(),
+                    label: None,
+                },
+                PlanStep {
+                    kind: PlanStepKind::Terminal,
+                    version: 2.into(),
+                    label: None,
+                },
+            ],
+            typ.describe_plan(1.into(), 2.into())
+        );
+        assert_eq!(
+            vec![PlanStep {
+                kind: PlanStepKind::Walker,
+                version: 1.into(),
+                label: None,
+            }],
+            typ.describe_plan(2.into(), 3.into())
+        );
+    }
+
+    #[test]
+    fn terminal_converter_observed() {
+        struct Terminals(Cell<u32>);
+        impl ConversionObserver for Terminals {
+            fn on_terminal_converter(&self, version: DataVersion) {
+                assert_eq!(DataVersion::from(2), version);
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let terminals = Terminals(Cell::new(0));
+        let mut typ = MapDataType::new("Test").with_observer(&terminals);
+        typ.add_terminal_converter(
+            2,
+            map_data_converter_func(|_data, _from_version, _to_version| {}),
+        );
+        typ.convert(&mut JCompound::new(), 1.into(), 2.into());
+        assert_eq!(1, terminals.0.get());
+    }
+
+    #[test]
+    fn terminal_converter_skipped_on_error() {
+        let mut typ = MapDataType::new("Test");
+        typ.add_try_structure_walker(
+            1,
+            try_map_data_walker(|_data, _from_version, _to_version| {
+                Err(ConversionError {
+                    message: "bad data".to_owned(),
+                })
+            }),
+        );
+        typ.add_terminal_converter(
+            2,
+            map_data_converter_func(|data, _from_version, _to_version| {
+                data.insert("terminal", true);
+            }),
+        );
+        let mut map = JCompound::new();
+        assert!(typ.try_convert(&mut map, 1.into(), 2.into()).is_err());
+        assert!(!map.contains_key("terminal"));
+    }
+
+    #[test]
+    fn combine_fields() {
+        let mut map = make_map(r#"{"x": 1, "y": 2, "z": 3}"#);
+        assert!(crate::combine_fields(
+            &mut map,
+            &["x", "y", "z"],
+            "Pos",
+            true,
+            true
+        ));
+        assert_compound_eq(&make_map(r#"{"Pos": [1, 2, 3]}"#), &map);
+
+        let mut map = make_map(r#"{"x": 1, "z": 3}"#);
+        assert!(!crate::combine_fields(
+            &mut map,
+            &["x", "y", "z"],
+            "pos",
+            false,
+            true
+        ));
+        assert!(crate::combine_fields(
+            &mut map,
+            &["x", "y", "z"],
+            "pos",
+            false,
+            false
+        ));
+        assert_compound_eq(&make_map(r#"{"pos": {"x": 1, "z": 3}}"#), &map);
+
+        let mut map = make_map(r#"{"x": 1, "y": 2.0}"#);
+        assert!(!crate::combine_fields(
+            &mut map,
+            &["x", "y"],
+            "pos",
+            true,
+            false
+        ));
+        assert_compound_eq(&make_map(r#"{"x": 1, "y": 2.0}"#), &map);
+    }
+
+    #[test]
+    fn data_type_debug() {
+        let mut typ = IdDataType::new("Test");
+        typ.add_structure_converter(
+            2,
+            map_data_converter_func(|_data, _from_version, _to_version| {}),
+        );
+        typ.add_walker_for_id(
+            1,
+            "foo",
+            map_data_walker(|_data, _from_version, _to_version| {}),
+        );
+        let debug = format!("{typ:?}");
+        assert!(debug.starts_with(r#"IdDataType { name: "Test", converters: [MapDataConverter{"#));
+        assert!(debug.contains("ids: 1"));
+    }
+
+    #[test]
+    fn slotted_list() {
+        let mut map = make_map(
+            r#"{"Items": [{"Slot": 3b, "id": "a"}, {"Slot": -106b, "id": "b"}, {"id": "c"}]}"#,
+        );
+        let mut slots = Vec::new();
+        convert_slotted_list_in_map(&mut map, "Items", "Slot", None, |slot, item| {
+            slots.push(slot);
+            item.insert("seen", true);
+        });
+        assert_eq!(vec![3, 150], slots);
+        assert_compound_eq(
+            &make_map(
+                r#"{"Items": [{"Slot": 3b, "id": "a", "seen": 1b}, {"Slot": -106b, "id": "b", "seen": 1b}, {"id": "c"}]}"#,
+            ),
+            &map,
+        );
+
+        slots.clear();
+        convert_slotted_list_in_map(&mut map, "Items", "Slot", Some(0), |slot, _item| {
+            slots.push(slot);
+        });
+        assert_eq!(vec![3, 150, 0], slots);
+
+        let mut map = make_map(r#"{"Items": [{"Slot": -106s}, {"Slot": -106}, {"Slot": 150s}]}"#);
+        slots.clear();
+        convert_slotted_list_in_map(&mut map, "Items", "Slot", None, |slot, _item| {
+            slots.push(slot);
+        });
+        assert_eq!(vec![150], slots);
+    }
+
+    #[test]
+    fn contextual_type() {
+        let mut nested = ContextualMapDataType::<HashMap<&str, &str>>::new("Nested");
+        nested.add_structure_converter(1, |data, names, _from_version, _to_version| {
+            if let Some(JValue::String(id)) = data.get_mut("id") {
+                if let Some(&name) = names.get(id.as_str().unwrap()) {
+                    *id = name.into();
+                }
+            }
+        });
+        let mut typ = ContextualMapDataType::new("Test");
+        typ.add_structure_walker(1, move |data, names, from_version, to_version| {
+            convert_map_in_map(
+                nested.with_context(names),
+                data,
+                "nested",
+                from_version,
+                to_version,
+            );
+        });
+
+        let names = HashMap::from([("old", "new")]);
+        let mut map = make_map(r#"{"nested": {"id": "old"}}"#);
+        typ.convert(&mut map, &names, 0.into(), 1.into());
+        assert_compound_eq(&make_map(r#"{"nested": {"id": "new"}}"#), &map);
+    }
+
+    #[test]
+    fn contextual_type_runs_only_converters_and_walkers() {
+        let mut typ = ContextualMapDataType::<RefCell<Vec<&str>>>::new("Test");
+        typ.add_structure_converter(1, |_data, log, _from, _to| log.borrow_mut().push("1"));
+        typ.add_structure_converter(2, |_data, log, _from, _to| log.borrow_mut().push("2"));
+        typ.add_structure_walker(1, |_data, log, _from, _to| {
+            log.borrow_mut().push("walker 1")
+        });
+        typ.add_structure_walker(3, |_data, log, _from, _to| {
+            log.borrow_mut().push("walker 3")
+        });
+
+        let log = RefCell::new(Vec::new());
+        typ.convert(&mut JCompound::new(), &log, 0.into(), 2.into());
+        assert_eq!(vec!["1", "2", "walker 1"], log.take());
+        typ.convert(&mut JCompound::new(), &log, 1.into(), 3.into());
+        assert_eq!(vec!["2", "walker 3"], log.take());
+    }
+
+    #[test]
+    fn split_list_to_fields() {
+        let mut map = make_map(r#"{"Pos": [1.0d, 2.0d, 3.0d]}"#);
+        assert!(!crate::split_list_to_fields(
+            &mut map,
+            "Pos",
+            &["PosX", "PosY"]
+        ));
+        assert!(crate::split_list_to_fields(
+            &mut map,
+            "Pos",
+            &["PosX", "PosY", "PosZ"]
+        ));
+        assert_compound_eq(
+            &make_map(r#"{"PosX": 1.0d, "PosY": 2.0d, "PosZ": 3.0d}"#),
+            &map,
+        );
+    }
+
+    #[test]
+    fn schema_validation() {
+        let mut typ = MapDataType::new("Test");
+        typ.add_schema(
+            2,
+            Schema::builder()
+                .required("id", NbtType::String)
+                .optional("Count", NbtType::Byte)
+                .build(),
+        );
+
+        let map = make_map(r#"{"Count": 1}"#);
+        assert_eq!(Ok(()), typ.validate(&map, 1));
+        assert_eq!(
+            Err(vec![
+                SchemaError {
+                    key: "id".into(),
+                    kind: SchemaErrorKind::Missing,
+                },
+                SchemaError {
+                    key: "Count".into(),
+                    kind: SchemaErrorKind::WrongType {
+                        expected: NbtType::Byte,
+                        actual: NbtType::Int,
+                    },
+                },
+            ]),
+            typ.validate(&map, 3)
+        );
+        assert_eq!(Ok(()), typ.validate(&make_map(r#"{"id": "foo"}"#), 2));
+    }
+
+    #[test]
+    fn nested_lists() {
+        let typ = simple_converted_type();
+        let mut map = make_map(
+            r#"{"blocks": [[{"test": 1}, {"test": 2}], [], [{"test": 3}]], "ragged": [[{"test": 4}], [[{"test": 5}]]]}"#,
+        );
+        convert_map_in_nested_lists(&typ, &mut map, "blocks", 2, 0.into(), 1.into());
+        convert_map_in_nested_lists(&typ, &mut map, "ragged", 2, 0.into(), 1.into());
+        assert_compound_eq(
+            &make_map(
+                r#"{"blocks": [[{"test": "1"}, {"test": "2"}], [], [{"test": "3"}]], "ragged": [[{"test": "4"}], [[{"test": 5}]]]}"#,
+            ),
+            &map,
+        );
+    }
+
+    #[test]
+    fn nbt_type_classifiers() {
+        let map = make_map(
+            r#"{"byte": 1b, "long": 1L, "string": "a", "bytes": [B; 1b], "compound": {}, "ints": [1], "empty": []}"#,
+        );
+        let types: Vec<_> = ["byte", "long", "string", "bytes", "compound", "ints"]
+            .into_iter()
+            .map(|key| crate::nbt_type(map.get(key).unwrap()))
+            .collect();
+        assert_eq!(
+            vec![
+                NbtType::Byte,
+                NbtType::Long,
+                NbtType::String,
+                NbtType::ByteArray,
+                NbtType::Compound,
+                NbtType::List
+            ],
+            types
+        );
+
+        let Some(JValue::List(ints)) = map.get("ints") else {
+            panic!("ints is not a list");
+        };
+        assert_eq!(NbtType::Int, list_element_type(ints));
+        let Some(JValue::List(empty)) = map.get("empty") else {
+            panic!("empty is not a list");
+        };
+        assert_eq!(NbtType::End, list_element_type(empty));
+
+        assert_eq!(NbtType::Double, NumberKind::Double.nbt_type());
+        assert_eq!("IntArray", NbtType::IntArray.name());
+        assert_eq!("LongArray", NbtType::LongArray.to_string());
+    }
+
+    #[test]
+    fn compound_entries_of_type() {
+        let mut map = make_map(r#"{"a": {"x": 1}, "b": 2, "c": {"x": 3}, "d": [{"x": 4}]}"#);
+        let mut keys: Vec<_> = crate::compound_entries_of_type(&map, NbtType::Compound)
+            .map(|(key, _)| key.to_owned())
+            .collect();
+        keys.sort_unstable();
+        assert_eq!(vec![JavaString::from("a"), JavaString::from("c")], keys);
+        assert_eq!(
+            0,
+            crate::compound_entries_of_type(&map, NbtType::String).count()
+        );
+
+        for (_, value) in compound_entries_of_type_mut(&mut map, NbtType::Compound) {
+            if let JValue::Compound(compound) = value {
+                compound.insert("visited", true);
+            }
+        }
+        assert_eq!(
+            make_map(
+                r#"{"a": {"x": 1, "visited": 1b}, "b": 2, "c": {"x": 3, "visited": 1b}, "d": [{"x": 4}]}"#
+            ),
+            map
+        );
+    }
+
+    #[test]
+    fn swap_array_endianness() {
+        let mut map =
+            make_map(r#"{"ints": [I; 1, -16777216], "longs": [L; 1L, 256L], "not_ints": [L; 1L]}"#);
+        swap_int_array_endianness(&mut map, "ints");
+        swap_long_array_endianness(&mut map, "longs");
+        swap_int_array_endianness(&mut map, "not_ints");
+        swap_long_array_endianness(&mut map, "missing");
+        assert_eq!(
+            make_map(
+                r#"{"ints": [I; 16777216, 255], "longs": [L; 72057594037927936L, 281474976710656L], "not_ints": [L; 1L]}"#
+            ),
+            map
+        );
+    }
+
+    #[test]
+    fn run_once() {
+        let mut typ = MapDataType::new("Test");
+        typ.add_structure_converter(
+            2,
+            run_once_at(
+                2,
+                "count",
+                map_data_converter_func(|data, _from_version, _to_version| {
+                    if let Some(JValue::Int(count)) = data.get_mut("count") {
+                        *count += 1;
+                    }
+                }),
+            ),
+        );
+
+        let mut map = make_map(r#"{"count": 0}"#);
+        typ.convert(&mut map, 1.into(), 2.into());
+        typ.convert(&mut map, 1.into(), 3.into());
+        assert_eq!(Some(&JValue::Int(1)), map.get("count"));
+        assert!(remove_run_once_markers(&mut map));
+        assert_eq!(make_map(r#"{"count": 1}"#), map);
+
+        let mut typ = MapDataType::new("Test");
+        typ.add_run_once_converter(
+            DataVersion::new(3, 1),
+            "count",
+            map_data_converter_func(|data, _from_version, _to_version| {
+                if let Some(JValue::Int(count)) = data.get_mut("count") {
+                    *count += 1;
+                }
+            }),
+        );
+        let mut map = make_map(r#"{"count": 0}"#);
+        typ.convert(&mut map, 1.into(), 4.into());
+        typ.convert(&mut map, 1.into(), 4.into());
+        assert_eq!(
+            make_map(r#"{"count": 1, "world_transmuter_engine:run_once": {"3.1/count": 1b}}"#),
+            map
+        );
+        assert_eq!(Some(Rc::from("count")), typ.converter_windows()[0].1);
+    }
+
+    #[test]
+    fn compact() {
+        let snbt = r#"{"a": {"b": [{"c": "d"}], "e": [[1, 2]]}, "f": [L; 1L]}"#;
+        let mut map = make_map(snbt);
+        let mut ints = Vec::with_capacity(100);
+        ints.push(1);
+        let mut inner = JCompound::new();
+        inner.insert("ints", JValue::List(JList::Int(ints)));
+        map.insert("g", inner);
+
+        crate::compact(&mut map, true);
+        let Some(JValue::Compound(inner)) = map.get("g") else {
+            panic!("g was not a compound");
+        };
+        assert!(
+            matches!(inner.get("ints"), Some(JValue::List(JList::Int(ints))) if ints.capacity() == 1)
+        );
+        map.remove("g");
+        assert_compound_eq(&make_map(snbt), &map);
+    }
+
+    #[test]
+    fn convert_down() {
+        let mut typ = MapDataType::new("Test");
+        for version in [1, 2, 3] {
+            typ.add_downgrade_converter(
+                version,
+                map_data_converter_func(move |data, _from_version, _to_version| {
+                    if let Some(JValue::String(log)) = data.get_mut("log") {
+                        log.push_str(&version.to_string());
+                    }
+                }),
+            );
+        }
+
+        let mut map = make_map(r#"{"log": ""}"#);
+        typ.convert(&mut map, 0.into(), 3.into());
+        assert_eq!(make_map(r#"{"log": ""}"#), map);
+        typ.convert_down(&mut map, 3.into(), 1.into());
+        assert_eq!(make_map(r#"{"log": "32"}"#), map);
+    }
+
+    #[test]
+    fn scoped() {
+        let typ = simple_converted_type().scoped("Level/Entities");
+        let mut map = make_map(
+            r#"{"test": 1, "Level": {"test": 2, "Entities": [{"test": 3}, {"test": 4}]}}"#,
+        );
+        typ.convert(&mut map, 0.into(), 1.into());
+        assert_eq!(
+            make_map(
+                r#"{"test": 1, "Level": {"test": 2, "Entities": [{"test": "3"}, {"test": "4"}]}}"#
+            ),
+            map
+        );
+
+        let typ = simple_converted_type().scoped("Level");
+        typ.convert(&mut map, 0.into(), 1.into());
+        assert!(matches!(
+            crate::clone_subtree(&map, &["Level", "test"]),
+            Some(JValue::String(str)) if str == "2"
+        ));
+    }
+
+    #[test]
+    fn data_version_range_set_operations() {
+        let range = |from: u32, to: u32| DataVersionRange::new(from, to);
+
+        // overlapping
+        assert!(range(1, 5).overlaps(&range(3, 8)));
+        assert_eq!(Some(range(3, 5)), range(1, 5).intersection(&range(3, 8)));
+        assert_eq!(Some(range(1, 8)), range(1, 5).union(&range(3, 8)));
+        assert_eq!(Some(range(2, 3)), range(1, 5).intersection(&range(2, 3)));
+
+        // adjacent
+        assert!(!range(1, 5).overlaps(&range(5, 8)));
+        assert_eq!(None, range(1, 5).intersection(&range(5, 8)));
+        assert_eq!(Some(range(1, 8)), range(5, 8).union(&range(1, 5)));
+
+        // disjoint
+        assert!(!range(1, 3).overlaps(&range(5, 8)));
+        assert_eq!(None, range(1, 3).intersection(&range(5, 8)));
+        assert_eq!(None, range(1, 3).union(&range(5, 8)));
+
+        // empty
+        assert!(!range(4, 4).overlaps(&range(1, 8)));
+        assert_eq!(Some(range(1, 3)), range(1, 3).union(&range(6, 6)));
+    }
+
+    #[test]
+    fn lazy_converted() {
+        let conversions = Cell::new(0);
+        let mut typ = MapDataType::new("Test");
+        typ.add_structure_converter(
+            1,
+            map_data_converter_func(|data, _from_version, _to_version| {
+                conversions.set(conversions.get() + 1);
+                data.insert("converted", JValue::Byte(1));
+            }),
+        );
+
+        let unused = LazyConverted::new(JCompound::new(), &typ, 0.into(), 1.into());
+        drop(unused);
+        assert_eq!(0, conversions.get());
+
+        let mut lazy = LazyConverted::new(JCompound::new(), &typ, 0.into(), 1.into());
+        assert!(!lazy.is_converted());
+        assert!(lazy.contains_key("converted"));
+        assert!(lazy.is_converted());
+        lazy.insert("modified", JValue::Byte(1));
+        assert_eq!(
+            make_map(r#"{"converted": 1b, "modified": 1b}"#),
+            lazy.into_inner()
+        );
+        assert_eq!(1, conversions.get());
+    }
+
+    #[test]
+    fn lazy_converted_after_panic() {
+        let mut typ = MapDataType::new("Test");
+        typ.add_structure_converter(
+            1,
+            map_data_converter_func(|_data, _from_version, _to_version| {
+                panic!("converter failed");
+            }),
+        );
+
+        let lazy = LazyConverted::new(make_map(r#"{"a": 1}"#), &typ, 0.into(), 1.into());
+        let access = || std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| lazy.len()));
+        assert!(access().is_err());
+        assert!(access().is_err());
+        assert!(!lazy.is_converted());
+    }
+
+    #[test]
+    fn simple_walker() {
+        let mut map = make_map(r#"{"inner": {"test": 42}}"#);
+        let mut typ = MapDataType::new("Outer");
+        let inner_type = simple_converted_type();
+        typ.add_structure_walker(
+            1,
+            map_data_walker(move |data, from_version, to_version| {
+                convert_map_in_map(&inner_type, data, "inner", from_version, to_version)
+            }),
+        );
+        typ.convert(&mut map, 0.into(), 1.into());
+        assert!(
+            matches!(map.get("inner"), Some(valence_nbt::Value::Compound(inner)) if matches!(inner.get("test"), Some(valence_nbt::Value::String(str)) if str == "42"))
+        );
+    }
+
+    #[test]
+    fn map_in_map_dynamic() {
+        let typ = simple_converted_type();
+        let selected = |data: &JCompound| match data.get("selected") {
+            Some(JValue::String(selected)) => Some(selected.clone()),
+            _ => None,
+        };
+        let mut map = make_map(r#"{"selected": "b", "a": {"test": 1}, "b": {"test": 2}}"#);
+        convert_map_in_map_dynamic(&typ, &mut map, selected, 0.into(), 1.into());
+        assert_eq!(
+            make_map(r#"{"selected": "b", "a": {"test": 1}, "b": {"test": "2"}}"#),
+            map
+        );
+
+        let mut map = make_map(r#"{"selected": "c", "a": {"test": 1}}"#);
+        convert_map_in_map_dynamic(&typ, &mut map, selected, 0.into(), 1.into());
+        assert_eq!(make_map(r#"{"selected": "c", "a": {"test": 1}}"#), map);
+    }
+
+    #[test]
+    fn simple_id_walker() {
+        let mut map1 = make_map(r#"{"id": "foo", "test": 42}"#);
+        let mut map2 = make_map(r#"{"id": "bar", "test": 42}"#);
+        let mut inner_type = ObjectDataType::new("Inner");
+        inner_type.add_structure_converter(
+            1,
+            value_data_converter_func(|data, _from_version, _to_version| {
+                if let valence_nbt::value::ValueMut::Int(i) = data {
+                    **i = 69;
+                }
+            }),
+        );
+        let mut typ = IdDataType::new("Test");
+        typ.add_walker_for_id(
+            1,
+            "foo",
+            map_data_walker(move |data, from_version, to_version| {
+                convert_object_in_map(&inner_type, data, "test", from_version, to_version);
+            }),
+        );
+
+        typ.convert(&mut map1, 0.into(), 1.into());
+        typ.convert(&mut map2, 0.into(), 1.into());
+        assert_eq!(69, map1.get("test").unwrap().as_i64().unwrap());
+        assert_eq!(42, map2.get("test").unwrap().as_i64().unwrap());
+    }
+
+    #[test]
+    fn split_entity() {
+        let mut typ = IdDataType::new("Test");
+        typ.add_converter_for_id(
+            "rider",
+            1,
+            map_data_converter_func(|data, _from_version, _to_version| {
+                data.insert("id", "mob");
+            }),
+        );
+        typ.add_splitter_for_id("mob", 2, |mut data, _from_version, _to_version| {
+            let Some(JValue::Compound(passenger)) = data.remove("Passenger") else {
+                return vec![data];
+            };
+            vec![data, passenger]
+        });
+        typ.add_converter_for_id(
+            "passenger",
+            3,
+            map_data_converter_func(|data, _from_version, _to_version| {
+                data.insert("converted", true);
+            }),
+        );
+
+        let mut map = make_map(
+            r#"{"Entities": [{"id": "rider", "Passenger": {"id": "passenger"}}, {"id": "other"}]}"#,
+        );
+        convert_split_list_in_map(&typ, &mut map, "Entities", 0.into(), 3.into());
+        assert_compound_eq(
+            &make_map(
+                r#"{"Entities": [{"id": "mob"}, {"id": "passenger", "converted": 1b}, {"id": "other"}]}"#,
+            ),
+            &map,
+        );
+    }
+
+    #[test]
+    fn split_entity_versions() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut typ = IdDataType::new("Test");
+        for version in [1, 3] {
+            let seen = seen.clone();
+            typ.add_structure_converter(
+                version,
+                map_data_converter_func(move |_data, from_version, to_version| {
+                    seen.borrow_mut().push((version, from_version, to_version));
+                }),
+            );
+        }
+        typ.add_splitter_for_id("mob", 2, |data, _from_version, _to_version| vec![data]);
+        typ.add_splitter_for_id("mob", 2, |data, _from_version, _to_version| vec![data]);
+        let walks = Rc::new(Cell::new(0));
+        let walks_clone = walks.clone();
+        typ.add_structure_walker(
+            1,
+            map_data_walker(move |_data, _from_version, _to_version| {
+                walks_clone.set(walks_clone.get() + 1);
+            }),
+        );
+
+        let pieces = typ.convert_split(make_map(r#"{"id": "mob"}"#), 0.into(), 4.into());
+        assert_eq!(
+            vec![(1, 0.into(), 4.into()), (3, 0.into(), 4.into())],
+            *seen.borrow()
+        );
+        assert_eq!(1, pieces.len());
+        assert_eq!(1, walks.get());
+    }
+
+    #[test]
+    fn numeric_id_type() {
+        let mut typ = NumericIdDataType::new("Test", "Type");
+        typ.add_converter_for_id(
+            200,
+            1,
+            map_data_converter_func(|data, _from_version, _to_version| {
+                data.insert("converted", true);
+            }),
+        );
+        typ.add_walker_for_id(
+            1,
+            200,
+            map_data_walker(|data, _from_version, _to_version| {
+                data.insert("walked", true);
+            }),
+        );
+        typ.copy_walkers(1, 200, 201);
+
+        let mut byte_id = make_map(r#"{"Type": -56b}"#);
+        let mut copied = make_map(r#"{"Type": 201}"#);
+        let mut other = make_map(r#"{"Type": 5}"#);
+        typ.convert(&mut byte_id, 0.into(), 1.into());
+        typ.convert(&mut copied, 0.into(), 1.into());
+        typ.convert(&mut other, 0.into(), 1.into());
+        assert_compound_eq(
+            &make_map(r#"{"Type": -56b, "converted": 1b, "walked": 1b}"#),
+            &byte_id,
+        );
+        assert_compound_eq(&make_map(r#"{"Type": 201, "walked": 1b}"#), &copied);
+        assert_compound_eq(&make_map(r#"{"Type": 5}"#), &other);
+        assert_eq!(vec![200, 201], typ.walker_ids().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn numeric_id_type_shares_id_type_loop() {
+        let mut typ = NumericIdDataType::new("Test", "Type");
+        typ.set_id_filter(|id| *id != 5);
+        typ.add_walker_for_id(
+            1,
+            200,
+            map_data_walker(|data, _from_version, _to_version| {
+                data.insert("walked", true);
+            }),
+        );
+        typ.add_default_id_walker(
+            1,
+            map_data_walker(|data, _from_version, _to_version| {
+                data.insert("default", true);
+            }),
+        );
+
+        let mut known = make_map(r#"{"Type": 200}"#);
+        let mut unknown = make_map(r#"{"Type": 6}"#);
+        let mut filtered = make_map(r#"{"Type": 5}"#);
+        typ.convert(&mut known, 0.into(), 1.into());
+        typ.convert(&mut unknown, 0.into(), 1.into());
+        typ.convert(&mut filtered, 0.into(), 1.into());
+        assert_compound_eq(&make_map(r#"{"Type": 200, "walked": 1b}"#), &known);
+        assert_compound_eq(&make_map(r#"{"Type": 6, "default": 1b}"#), &unknown);
+        assert_compound_eq(&make_map(r#"{"Type": 5}"#), &filtered);
+    }
+
+    #[test]
+    fn default_id_walker() {
+        let mut typ = IdDataType::new("Test");
+        typ.add_walker_for_id(
+            1,
+            "foo",
+            map_data_walker(|data, _from_version, _to_version| {
+                data.insert("walked", "foo");
+            }),
+        );
+        typ.add_default_id_walker(
+            1,
+            map_data_walker(|data, _from_version, _to_version| {
+                data.insert("walked", "default");
+            }),
+        );
+
+        let mut foo = make_map(r#"{"id": "foo"}"#);
+        let mut bar = make_map(r#"{"id": "bar"}"#);
+        let mut no_id = make_map(r#"{}"#);
+        typ.convert(&mut foo, 0.into(), 1.into());
+        typ.convert(&mut bar, 0.into(), 1.into());
+        typ.convert(&mut no_id, 0.into(), 1.into());
+        assert_eq!(Some(&JValue::String("foo".into())), foo.get("walked"));
+        assert_eq!(Some(&JValue::String("default".into())), bar.get("walked"));
+        assert_eq!(Some(&JValue::String("default".into())), no_id.get("walked"));
+    }
+
+    #[test]
+    fn walker_ids() {
+        let mut typ = IdDataType::new("Test");
+        assert_eq!(0, typ.walker_ids().count());
+        typ.add_walker_for_id(2, "b", map_data_walker(|_data, _from, _to| {}));
+        typ.add_walker_for_id(1, "a", map_data_walker(|_data, _from, _to| {}));
+        typ.add_walker_for_id(3, "a", map_data_walker(|_data, _from, _to| {}));
+        typ.add_converter_for_id(
+            "converted",
+            1,
+            map_data_converter_func(|_data, _from, _to| {}),
+        );
+        typ.copy_walkers(2, "b", "c");
+        assert_eq!(
+            vec!["a", "b", "c"],
+            typ.walker_ids()
+                .map(|id| id.as_str().unwrap())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn unknown_id_handler() {
+        let unknown = RefCell::new(Vec::new());
+        let mut typ = IdDataType::new("Test");
+        typ.add_walker_for_id(
+            1,
+            "foo",
+            map_data_walker(|_data, _from_version, _to_version| {}),
+        );
+        typ.add_walker_for_id(
+            3,
+            "later",
+            map_data_walker(|_data, _from_version, _to_version| {}),
+        );
+        typ.set_unknown_id_handler(|id| unknown.borrow_mut().push(id.to_owned()));
+
+        for data in [
+            r#"{"id": "foo"}"#,
+            r#"{"id": "bar"}"#,
+            r#"{"id": "later"}"#,
+            r#"{"id": 1}"#,
+            r#"{}"#,
+        ] {
+            typ.convert(&mut make_map(data), 0.into(), 1.into());
+        }
+        assert_eq!(
+            vec![JavaString::from("bar"), JavaString::from("later")],
+            unknown.take()
+        );
+    }
+
+    #[test]
+    fn normalize_keys() {
+        let mut map = make_map(r#"{" Foo ": 1, "BAR": 2, "baz": 3}"#);
+        ascii_lowercase_keys(&mut map);
+        assert_eq!(make_map(r#"{"foo": 1, "bar": 2, "baz": 3}"#), map);
+    }
+
+    #[test]
+    fn flat_map_list() {
+        let mut map = make_map(r#"{"list": [1, 2, 3]}"#);
+        let dropped = flat_map_list_in_map(&mut map, "list", |element| match element {
+            JValue::Int(1) => Vec::new(),
+            JValue::Int(2) => vec![JValue::Int(2), JValue::Int(2)],
+            _ => vec![element, JValue::String("x".into())],
+        });
+        assert_eq!(1, dropped);
+        assert_eq!(make_map(r#"{"list": [2, 2, 3]}"#), map);
+    }
+
+    #[test]
+    fn container_lengths() {
+        let map = make_map(
+            r#"{"list": [1, 2, 3], "bytes": [B; 1b, 2b], "ints": [I; 5], "longs": [L; 1L]}"#,
+        );
+        assert_eq!(Some(3), list_len(&map, "list"));
+        assert_eq!(Some(2), byte_array_len(&map, "bytes"));
+        assert_eq!(Some(1), int_array_len(&map, "ints"));
+        assert_eq!(Some(1), long_array_len(&map, "longs"));
+        assert_eq!(None, list_len(&map, "bytes"));
+        assert_eq!(None, long_array_len(&map, "missing"));
+    }
+
+    #[test]
+    fn sum_type() {
+        let mut typ = SumTypeDataType::new("Test", "type");
+        let mut circle = MapDataType::new("Circle");
+        circle.add_structure_converter(
+            1,
+            map_data_converter_func(|data, _from_version, _to_version| {
+                crate::rename_key(data, "r", "radius");
+            }),
+        );
+        typ.add_variant("circle", circle);
+
+        let mut circle = make_map(r#"{"type": "circle", "r": 1}"#);
+        let mut square = make_map(r#"{"type": "square", "r": 1}"#);
+        typ.convert(&mut circle, 0.into(), 1.into());
+        typ.convert(&mut square, 0.into(), 1.into());
+        assert_eq!(make_map(r#"{"type": "circle", "radius": 1}"#), circle);
+        assert_eq!(make_map(r#"{"type": "square", "r": 1}"#), square);
+    }
+
+    #[test]
+    fn clone_subtree() {
+        let map = make_map(r#"{"a": {"b": {"c": 1}}, "d": 2}"#);
+        assert_eq!(
+            Some(JValue::Compound(make_map(r#"{"c": 1}"#))),
+            crate::clone_subtree(&map, &["a", "b"])
+        );
+        assert_eq!(Some(JValue::Int(2)), crate::clone_subtree(&map, &["d"]));
+        assert_eq!(None, crate::clone_subtree(&map, &["d", "e"]));
+        assert_eq!(None, crate::clone_subtree(&map, &["a", "x"]));
+    }
+
+    #[test]
+    fn legacy_block_to_string() {
+        let table = HashMap::from([
+            ((1, 0), JavaString::from("minecraft:stone")),
+            ((1, 1), JavaString::from("minecraft:granite")),
+            ((251, 0), JavaString::from("minecraft:white_concrete")),
+        ]);
+
+        let mut granite = make_map(r#"{"id": 1s, "Damage": 17s}"#);
+        assert!(crate::legacy_block_to_string(
+            &mut granite,
+            "id",
+            "Damage",
+            &table
+        ));
+        assert_eq!(make_map(r#"{"id": "minecraft:granite"}"#), granite);
+
+        let mut fallback = make_map(r#"{"id": 1, "Damage": 7}"#);
+        assert!(crate::legacy_block_to_string(
+            &mut fallback,
+            "id",
+            "Damage",
+            &table
+        ));
+        assert_eq!(make_map(r#"{"id": "minecraft:stone"}"#), fallback);
+
+        let mut no_meta = make_map(r#"{"id": -5b}"#);
+        assert!(crate::legacy_block_to_string(
+            &mut no_meta,
+            "id",
+            "Damage",
+            &table
+        ));
+        assert_eq!(make_map(r#"{"id": "minecraft:white_concrete"}"#), no_meta);
+
+        let mut unknown = make_map(r#"{"id": 2, "Damage": 0}"#);
+        assert!(!crate::legacy_block_to_string(
+            &mut unknown,
+            "id",
+            "Damage",
+            &table
+        ));
+        assert_eq!(make_map(r#"{"id": 2, "Damage": 0}"#), unknown);
+    }
+
+    #[test]
+    fn sequence() {
+        fn appender(name: &'static str, suffix: &'static str) -> MapDataType<'static> {
+            let mut typ = MapDataType::new(name);
+            typ.add_structure_converter(
+                1,
+                map_data_converter_func(move |data, _from_version, _to_version| {
+                    if let Some(JValue::String(str)) = data.get_mut("str") {
+                        str.push_str(suffix);
+                    }
+                }),
+            );
+            typ
+        }
+
+        let mut map = make_map(r#"{"str": ""}"#);
+        crate::sequence(appender("A", "a"), appender("B", "b")).convert(
+            &mut map,
+            0.into(),
+            1.into(),
+        );
+        assert_eq!(make_map(r#"{"str": "ab"}"#), map);
+
+        let mut map = make_map(r#"{"str": ""}"#);
+        sequence_all(vec![
+            Box::new(appender("A", "a")),
+            Box::new(appender("B", "b")),
+            Box::new(appender("C", "c")),
+        ])
+        .convert(&mut map, 0.into(), 1.into());
+        assert_eq!(make_map(r#"{"str": "abc"}"#), map);
+    }
+
+    #[test]
+    fn dynamic_list_buffered() {
+        let mut typ = DynamicDataType::new("Test");
+        typ.add_structure_converter(
+            1,
+            dynamic_data_converter_func(|data, _from_version, _to_version| {
+                if let JValue::Int(i) = *data {
+                    *data = JValue::String(JavaString::from(i.to_string()));
+                }
+            }),
+        );
+
+        let mut scratch = JList::new();
+        for _ in 0..2 {
+            let mut map = make_map(r#"{"list": [1, 2]}"#);
+            let dropped = convert_dynamic_list_in_map_buffered(
+                &typ,
+                &mut map,
+                "list",
+                0.into(),
+                1.into(),
+                &mut scratch,
+            );
+            assert_eq!(0, dropped);
+            assert_eq!(make_map(r#"{"list": ["1", "2"]}"#), map);
+            assert!(scratch.is_empty());
+        }
+    }
+
+    #[test]
+    fn dynamic_list_keep_original() {
+        let mut typ = DynamicDataType::new("Test");
+        typ.add_structure_converter(
+            1,
+            dynamic_data_converter_func(|data, _from_version, _to_version| {
+                if let JValue::Int(2) = *data {
+                    *data = JValue::String(JavaString::from("2"));
+                }
+            }),
+        );
+
+        let mut map = make_map(r#"{"list": [1, 2, 3]}"#);
+        let mismatched = convert_dynamic_list_in_map_with_policy(
+            &typ,
+            &mut map,
+            "list",
+            0.into(),
+            1.into(),
+            ListConversionPolicy::KeepOriginal,
+        );
+        assert_eq!(1, mismatched);
+        assert_eq!(make_map(r#"{"list": [1, 2, 3]}"#), map);
+
+        let dropped = convert_dynamic_list_in_map_with_policy(
+            &typ,
+            &mut map,
+            "list",
+            0.into(),
+            1.into(),
+            ListConversionPolicy::DropMismatched,
+        );
+        assert_eq!(1, dropped);
+        assert_eq!(make_map(r#"{"list": [1, 3]}"#), map);
+    }
+
+    #[test]
+    fn palette_type_clash() {
+        let mut typ = DynamicDataType::new("Test");
+        typ.add_structure_converter(
+            1,
+            dynamic_data_converter_func(|data, _from_version, _to_version| {
+                if let JValue::String(str) = data {
+                    if str == "b" {
+                        *data = JValue::Int(2);
+                    } else {
+                        str.push_str("!");
+                    }
+                }
+            }),
+        );
+
+        let mut map = make_map(r#"{"palette": ["a", "b", "c"], "data": [L; 2L]}"#);
+        assert!(!convert_palette_and_indices(
+            &typ,
+            &mut map,
+            "palette",
+            0.into(),
+            1.into()
+        ));
+        assert_eq!(
+            make_map(r#"{"palette": ["a", "b", "c"], "data": [L; 2L]}"#),
+            map
+        );
+
+        let mut map = make_map(r#"{"palette": ["a", "c"]}"#);
+        assert!(convert_palette_and_indices(
+            &typ,
+            &mut map,
+            "palette",
+            0.into(),
+            1.into()
+        ));
+        assert_eq!(make_map(r#"{"palette": ["a!", "c!"]}"#), map);
+    }
+
+    #[test]
+    fn ensure_field() {
+        let mut map = make_map(r#"{"present": 1, "wrong": "x"}"#);
+        assert!(!crate::ensure_field(&mut map, "present", || JValue::Int(0)));
+        assert!(crate::ensure_field(&mut map, "missing", || JValue::Int(0)));
+        assert!(!ensure_field_typed(
+            &mut map,
+            "present",
+            NbtType::Int,
+            || JValue::Int(0)
+        ));
+        assert!(ensure_field_typed(&mut map, "wrong", NbtType::Int, || {
+            JValue::Int(0)
+        }));
+        assert_eq!(make_map(r#"{"present": 1, "wrong": 0, "missing": 0}"#), map);
+    }
+
+    #[test]
+    fn sort_list() {
+        let mut map =
+            make_map(r#"{"list": [{"slot": 2, "n": 0}, {"slot": 1}, {"slot": 2, "n": 1}]}"#);
+        sort_list_in_map(&mut map, "list", |a, b| {
+            let slot = |value: &JValue| match value {
+                JValue::Compound(compound) => compound.get("slot").and_then(|slot| slot.as_i64()),
+                _ => None,
+            };
+            slot(a).cmp(&slot(b))
+        });
+        assert_eq!(
+            make_map(r#"{"list": [{"slot": 1}, {"slot": 2, "n": 0}, {"slot": 2, "n": 1}]}"#),
+            map
+        );
+    }
+
+    #[test]
+    fn id_filter() {
+        let make_type = |scope| {
+            let mut typ = IdDataType::new("Test");
+            typ.set_id_filter(|id| id == "foo");
+            typ.set_id_filter_scope(scope);
+            typ.add_structure_converter(
+                1,
+                map_data_converter_func(|data, _from_version, _to_version| {
+                    data.insert("converted", JValue::Byte(1));
+                }),
+            );
+            typ.add_structure_walker(
+                1,
+                map_data_walker(|data, _from_version, _to_version| {
+                    data.insert("walked", JValue::Byte(1));
+                }),
+            );
+            typ.add_default_id_walker(
+                1,
+                map_data_walker(|data, _from_version, _to_version| {
+                    data.insert("id_walked", JValue::Byte(1));
+                }),
+            );
+            typ
+        };
+
+        let convert = |typ: &IdDataType, id: &str| {
+            let mut map = JCompound::new();
+            map.insert("id", id);
+            typ.convert(&mut map, 0.into(), 1.into());
+            ["converted", "walked", "id_walked"].map(|key| map.contains_key(key))
+        };
+
+        let typ = make_type(IdFilterScope::IdWalkers);
+        assert_eq!([true, true, true], convert(&typ, "foo"));
+        assert_eq!([true, true, false], convert(&typ, "bar"));
+        let typ = make_type(IdFilterScope::Walkers);
+        assert_eq!([true, false, false], convert(&typ, "bar"));
+        let typ = make_type(IdFilterScope::Everything);
+        assert_eq!([false, false, false], convert(&typ, "bar"));
+    }
+
+    #[test]
+    fn empty_lists_unchanged() {
+        let map_type = simple_converted_type();
+        let object_type = ObjectDataType::new("Object");
+        let dynamic_type = DynamicDataType::new("Dynamic");
+        for empty in [
+            JList::End,
+            JList::Int(Vec::new()),
+            JList::Compound(Vec::new()),
+        ] {
+            let mut map = JCompound::new();
+            map.insert("list", JValue::List(empty.clone()));
+            convert_map_list_in_map(&map_type, &mut map, "list", 0.into(), 1.into());
+            convert_object_list_in_map(&object_type, &mut map, "list", 0.into(), 1.into());
+            assert_eq!(
+                0,
+                convert_dynamic_list_in_map(&dynamic_type, &mut map, "list", 0.into(), 1.into())
+            );
+            assert_eq!(
+                0,
+                flat_map_list_in_map(&mut map, "list", |element| vec![element])
+            );
+            sort_list_in_map(&mut map, "list", |_, _| std::cmp::Ordering::Equal);
+            assert_eq!(Some(&JValue::List(empty)), map.get("list"));
+        }
+    }
+
+    #[test]
+    fn object_or_list() {
+        let mut typ = ObjectDataType::new("Test");
+        typ.add_structure_converter(
+            1,
+            value_data_converter_func(|data, _from_version, _to_version| match data {
+                valence_nbt::value::ValueMut::Int(i) => **i += 1,
+                valence_nbt::value::ValueMut::List(list) => {
+                    list.try_push(JValue::Int(0));
+                }
+                _ => {}
+            }),
+        );
+
+        let mut map =
+            make_map(r#"{"single": 1, "list": [1, 2], "empty": [], "nested": [[1], [2]]}"#);
+        for key in ["single", "list", "empty", "nested"] {
+            convert_object_or_list_in_map(&typ, &mut map, key, 0.into(), 1.into());
+        }
+        assert_eq!(
+            make_map(r#"{"single": 2, "list": [2, 3], "empty": [], "nested": [[1, 0], [2, 0]]}"#),
+            map
+        );
+    }
+
+    #[test]
+    fn repack_long_array() {
+        // 13 five-bit entries span a long boundary when unpadded, but need 2 longs of 12 entries
+        // when padded
+        let entries: Vec<i64> = (0..13).map(|i| (i * 7) % 32).collect();
+        let unpadded =
+            entries
+                .iter()
+                .enumerate()
+                .fold(vec![0i64; 2], |mut longs, (index, &entry)| {
+                    let bit = index * 5;
+                    longs[bit / 64] |= entry << (bit % 64);
+                    if bit % 64 > 59 {
+                        longs[bit / 64 + 1] |= entry >> (64 - bit % 64);
+                    }
+                    longs
+                });
+        let mut map = JCompound::new();
+        map.insert("data", JValue::LongArray(unpadded.clone()));
+
+        assert!(crate::repack_long_array(
+            &mut map,
+            "data",
+            BitStorageLayout::unpadded(5),
+            BitStorageLayout::padded(6),
+            13
+        ));
+        let Some(JValue::LongArray(padded)) = map.get("data") else {
+            panic!("data is not a long array");
+        };
+        assert_eq!(2, padded.len());
+        for (index, &entry) in entries.iter().enumerate() {
+            assert_eq!(entry, (padded[index / 10] >> (index % 10 * 6)) & 63);
+        }
+
+        assert!(crate::repack_long_array(
+            &mut map,
+            "data",
+            BitStorageLayout::padded(6),
+            BitStorageLayout::unpadded(5),
+            13
+        ));
+        assert_eq!(Some(&JValue::LongArray(unpadded)), map.get("data"));
+
+        // 31 doesn't fit in 4 bits, and the array is too short for 30 entries
+        let before = map.clone();
+        assert!(!crate::repack_long_array(
+            &mut map,
+            "data",
+            BitStorageLayout::unpadded(5),
+            BitStorageLayout::padded(4),
+            13
+        ));
+        assert!(!crate::repack_long_array(
+            &mut map,
+            "data",
+            BitStorageLayout::unpadded(5),
+            BitStorageLayout::padded(5),
+            30
+        ));
+        assert_eq!(before, map);
+    }
+
+    #[test]
+    fn object_type_builder() {
+        let typ = ObjectDataType::builder("Test")
+            .on_compound(simple_converted_type())
+            .on_list(
+                ObjectDataType::builder("Element")
+                    .on_compound(simple_converted_type())
+                    .build(),
+            )
+            .converter(
+                1,
+                value_data_converter_func(|data, _from_version, _to_version| {
+                    if let valence_nbt::value::ValueMut::Int(i) = data {
+                        **i += 1;
+                    }
+                }),
+            )
+            .build();
+
+        let mut map = make_map(r#"{"compound": {"test": 1}, "list": [{"test": 2}], "int": 3}"#);
+        for key in ["compound", "list", "int"] {
+            convert_object_in_map(&typ, &mut map, key, 0.into(), 1.into());
+        }
+        assert_eq!(
+            make_map(r#"{"compound": {"test": "1"}, "list": [{"test": "2"}], "int": 4}"#),
+            map
+        );
+    }
+
+    #[test]
+    fn coerce_list_numeric() {
+        let mut map =
+            make_map(r#"{"ints": [1, 300, -300], "doubles": [1.5d, -2.5d], "strings": ["1"]}"#);
+        crate::coerce_list_numeric(&mut map, "ints", NumberKind::Byte);
+        crate::coerce_list_numeric(&mut map, "doubles", NumberKind::Long);
+        crate::coerce_list_numeric(&mut map, "strings", NumberKind::Int);
+        assert_eq!(
+            make_map(r#"{"ints": [1b, 127b, -128b], "doubles": [1L, -2L], "strings": ["1"]}"#),
+            map
+        );
+    }
+
+    #[test]
+    fn dedup_list() {
+        let mut map = make_map(r#"{"list": [3, 1, 3, 2, 1]}"#);
+        assert_eq!(2, dedup_list_in_map(&mut map, "list", PartialEq::eq));
+        assert_eq!(make_map(r#"{"list": [3, 1, 2]}"#), map);
+
+        let mut map = make_map(r#"{"list": [{"id": "a", "lvl": 1s}, {"id": "a", "lvl": 1}]}"#);
+        assert_eq!(
+            1,
+            dedup_list_in_map(&mut map, "list", crate::semantically_eq)
+        );
+        assert_eq!(make_map(r#"{"list": [{"id": "a", "lvl": 1s}]}"#), map);
+    }
+
+    #[test]
+    fn convert_map_entries() {
+        let typ = simple_converted_type();
+        let mut map =
+            make_map(r#"{"entries": {"old:a": {"test": 1}, "a": {"test": 2}, "b": {"test": 3}}}"#);
+        crate::convert_map_entries(
+            &mut map,
+            "entries",
+            |key| (key == "old:a").then(|| JavaString::from("a")),
+            &typ,
+            0.into(),
+            1.into(),
+        );
+        // the renamed entry replaces the existing one, after both have been converted
+        assert_eq!(
+            make_map(r#"{"entries": {"a": {"test": "1"}, "b": {"test": "3"}}}"#),
+            map
+        );
+    }
+
+    #[test]
+    fn convert_keyed_values() {
+        let typ = simple_converted_type();
+        let mut map = make_map(r#"{"old:a": {"test": 1}, "a": {"test": 2}, "b": 3}"#);
+        crate::convert_keyed_values(
+            &typ,
+            &mut map,
+            |key| (key == "old:a").then(|| JavaString::from("a")),
+            0.into(),
+            1.into(),
+        );
+        // the renamed entry replaces the existing one, and only the surviving entry is converted
+        assert_eq!(make_map(r#"{"a": {"test": "1"}, "b": 3}"#), map);
+    }
+
+    #[test]
+    fn migrate_field() {
+        let decide = |map: &JCompound| match map.get("Type") {
+            Some(JValue::Int(2)) => Some(FieldAction::Rename {
+                from: "Data".into(),
+                to: "Variant".into(),
+            }),
+            Some(JValue::Int(3)) => Some(FieldAction::Remove("Data".into())),
+            _ => None,
+        };
+
+        let mut map = make_map(r#"{"Type": 2, "Data": 5}"#);
+        assert!(crate::migrate_field(&mut map, decide));
+        assert_eq!(make_map(r#"{"Type": 2, "Variant": 5}"#), map);
+
+        let mut map = make_map(r#"{"Type": 3, "Data": 5}"#);
+        assert!(crate::migrate_field(&mut map, decide));
+        assert_eq!(make_map(r#"{"Type": 3}"#), map);
+
+        let mut map = make_map(r#"{"Type": 1, "Data": 5}"#);
+        assert!(!crate::migrate_field(&mut map, decide));
+        assert_eq!(make_map(r#"{"Type": 1, "Data": 5}"#), map);
+    }
+
+    #[test]
+    fn dynamic_list_type_change() {
+        let mut map = make_map(r#"{"same": [1, 2, 3], "changed": [1, 2, 3]}"#);
+        let mut same_type = DynamicDataType::new("Same");
+        same_type.add_structure_converter(
+            1,
+            dynamic_data_converter_func(|data, _from_version, _to_version| {
+                if let JValue::Int(i) = data {
+                    *i += 1;
+                }
+            }),
+        );
+        let mut changed_type = DynamicDataType::new("Changed");
+        changed_type.add_structure_converter(
+            1,
+            dynamic_data_converter_func(|data, _from_version, _to_version| {
+                if let JValue::Int(i) = *data {
+                    *data = JValue::String(JavaString::from(i.to_string()));
+                }
+            }),
+        );
+        assert_eq!(
+            0,
+            convert_dynamic_list_in_map(&same_type, &mut map, "same", 0.into(), 1.into())
+        );
+        assert_eq!(
+            0,
+            convert_dynamic_list_in_map(&changed_type, &mut map, "changed", 0.into(), 1.into())
+        );
+        assert!(
+            matches!(map.get("same"), Some(JValue::List(JList::Int(ints))) if ints == &[2, 3, 4])
+        );
+        assert!(
+            matches!(map.get("changed"), Some(JValue::List(JList::String(strings))) if strings.len() == 3 && &strings[2] == "3")
+        );
+    }
+
+    #[test]
+    fn narrow_numbers() {
+        let mut map = make_map(r#"{"small": 5L, "big": 300, "huge": 3000000000L}"#);
+        narrow_to_byte(&mut map, "small", NarrowingPolicy::Clamp);
+        narrow_to_byte(&mut map, "big", NarrowingPolicy::Clamp);
+        narrow_to_int(&mut map, "huge", NarrowingPolicy::LeaveUnchanged);
+        assert!(matches!(map.get("small"), Some(JValue::Byte(5))));
+        assert!(matches!(map.get("big"), Some(JValue::Byte(127))));
+        assert!(matches!(map.get("huge"), Some(JValue::Long(3000000000))));
+    }
+
+    #[test]
+    fn type_registry() {
+        let mut registry = TypeRegistry::new();
+        registry.register("test", simple_converted_type());
+        let mut map = make_map(r#"{"test": 42}"#);
+        registry
+            .convert_named("test", &mut map, 0, 1)
+            .expect("test type should be registered");
+        assert!(matches!(map.get("test"), Some(JValue::String(str)) if str == "42"));
+        assert!(registry.convert_named("missing", &mut map, 0, 1).is_err());
+    }
+
+    #[test]
+    fn unpaired_surrogate_from_java() {
+        let mut map = make_map(r#"{"ok": "fine"}"#);
+        assert!(compound_from_java(map.clone()).is_ok());
+        map.insert(
+            "bad",
+            JValue::String(JavaString::from_semi_utf8(vec![0xed, 0xa0, 0x80]).unwrap()),
+        );
+        assert!(compound_from_java(map).is_err());
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_round_trip() {
+        let map =
+            make_map(r#"{"byte": 1b, "long": 10000000000L, "str": "a", "list": [{"x": 1.5d}]}"#);
+        let json = crate::compound_to_json(&map);
+        assert_eq!(
+            serde_json::json!({"byte": 1, "long": 10000000000i64, "str": "a", "list": [{"x": 1.5}]}),
+            json
+        );
+        let serde_json::Value::Object(object) = json else {
+            unreachable!()
+        };
+        assert_eq!(
+            make_map(r#"{"byte": 1, "long": 10000000000L, "str": "a", "list": [{"x": 1.5d}]}"#),
+            crate::json_to_compound(&object)
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn text_component() {
+        let mut typ = MapDataType::new("TextComponent");
+        typ.add_structure_converter(
+            2,
+            map_data_converter_func(|data, _from, _to| {
+                data.insert("converted", JValue::Byte(1));
+            }),
+        );
+
+        let mut map = make_map(
+            r#"{"object": '{"text": "a"}', "plain": '"b"', "invalid": "c", "compound": {"text": "d"}}"#,
+        );
+        for key in ["object", "plain", "invalid", "compound"] {
+            crate::convert_text_component(&typ, &mut map, key, 1.into(), 2.into(), 2.into());
+        }
+        assert_eq!(
+            make_map(
+                r#"{
+                    "object": {"text": "a", "converted": 1b},
+                    "plain": {"text": "b", "converted": 1b},
+                    "invalid": {"text": "c", "converted": 1b},
+                    "compound": {"text": "d", "converted": 1b}
+                }"#
+            ),
+            map
+        );
+
+        let mut map = make_map(r#"{"object": '{"text": "a"}'}"#);
+        crate::convert_text_component(&typ, &mut map, "object", 1.into(), 2.into(), 3.into());
+        let Some(JValue::String(json)) = map.get("object") else {
+            panic!("component was not stringified");
+        };
+        assert_eq!(
+            serde_json::json!({"text": "a", "converted": 1}),
+            serde_json::from_str::<serde_json::Value>(json.as_str().unwrap()).unwrap()
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn text_component_booleans_and_mixed_lists() {
+        let mut typ = MapDataType::new("TextComponent");
+        typ.add_structure_converter(
+            2,
+            map_data_converter_func(|data, _from, _to| {
+                data.insert("color", "red");
+            }),
+        );
+
+        let mut map =
+            make_map(r#"{"text": '{"text": "a", "bold": true, "extra": ["b", {"text": "c"}]}'}"#);
+        crate::convert_text_component(&typ, &mut map, "text", 1.into(), 2.into(), 2.into());
+        assert_compound_eq(
+            &make_map(
+                r#"{"text": {"text": "a", "bold": 1b, "extra": [{"": "b"}, {"": {"text": "c"}}], "color": "red"}}"#,
+            ),
+            &map,
+        );
+
+        let mut map =
+            make_map(r#"{"text": '{"text": "a", "bold": true, "extra": ["b", {"text": "c"}]}'}"#);
+        crate::convert_text_component(&typ, &mut map, "text", 1.into(), 2.into(), 3.into());
+        let Some(JValue::String(json)) = map.get("text") else {
+            panic!("component was not stringified");
+        };
+        assert_eq!(
+            serde_json::json!({"text": "a", "bold": true, "extra": ["b", {"text": "c"}], "color": "red"}),
+            serde_json::from_str::<serde_json::Value>(json.as_str().unwrap()).unwrap()
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn text_component_unchanged() {
+        let typ = MapDataType::new("TextComponent");
+        let snbt = r#"{"plain": '"b"', "invalid": "c", "compound": {"text": "d"}}"#;
+        let mut map = make_map(snbt);
+        for key in ["plain", "invalid"] {
+            crate::convert_text_component(&typ, &mut map, key, 1.into(), 2.into(), 3.into());
+        }
+        crate::convert_text_component(&typ, &mut map, "compound", 1.into(), 2.into(), 2.into());
+        assert_compound_eq(&make_map(snbt), &map);
+    }
+
+    #[cfg(feature = "hematite-nbt")]
+    #[test]
+    fn hematite_round_trip() {
+        let map = make_map(
+            r#"{
+                "byte": 1b,
+                "short": 2s,
+                "int": 3,
+                "long": 4L,
+                "float": 5.0f,
+                "double": 6.0d,
+                "byte_array": [B; 7b],
+                "string": "foo",
+                "list": [8, 9],
+                "compound": {"a": 10},
+                "int_array": [I; 11],
+                "long_array": [L; 12L]
+            }"#,
+        );
+        let hematite = crate::java_to_hematite(map.clone()).unwrap();
+        assert_eq!(map, crate::hematite_to_java(hematite));
+    }
+
+    #[cfg(feature = "quartz_nbt")]
+    #[test]
+    fn quartz_round_trip() {
+        let map = make_map(
+            r#"{"byte": 1b, "string": "foo", "list": [1, 2], "compound": {"a": [L; 1L]}, "empty": []}"#,
+        );
+        let quartz = crate::java_to_quartz(map.clone()).unwrap();
+        assert_eq!(map, crate::quartz_to_java(quartz));
+    }
+
+    #[cfg(feature = "region")]
+    #[test]
+    fn region_chunk_round_trip() {
+        use quartz_nbt::io::Flavor;
+
+        let mut typ = MapDataType::new("Chunk");
+        typ.add_structure_converter(
+            2,
+            map_data_converter_func(|data, _from, _to| {
+                crate::rename_key(data, "old", "new");
+            }),
+        );
+
+        for (compression, flavor) in [
+            (1, Flavor::GzCompressed),
+            (2, Flavor::ZlibCompressed),
+            (3, Flavor::Uncompressed),
+        ] {
+            let map = make_map(r#"{"DataVersion": 1, "old": "x"}"#);
+            let mut chunk = vec![compression];
+            quartz_nbt::io::write_nbt(
+                &mut chunk,
+                Some(""),
+                &crate::java_to_quartz(map).unwrap(),
+                flavor,
+            )
+            .unwrap();
+
+            let converted = crate::convert_region_chunk(&typ, &chunk, 3.into()).unwrap();
+            assert_eq!(compression, converted[0]);
+            let (root, _) = quartz_nbt::io::read_nbt(&mut &converted[1..], flavor).unwrap();
+            assert_compound_eq(
+                &crate::quartz_to_java(root),
+                &make_map(r#"{"DataVersion": 3, "new": "x"}"#),
+            );
+        }
+
+        assert!(crate::convert_region_chunk(&typ, &[], 3.into()).is_err());
+        assert!(crate::convert_region_chunk(&typ, &[127], 3.into()).is_err());
+    }
+
+    #[cfg(feature = "snbt")]
+    #[test]
+    fn snbt_round_trip() {
+        let map = crate::compound_from_snbt(r#"{"a": 1, "b": ["x", "y"]}"#).unwrap();
+        assert!(matches!(map.get("a"), Some(JValue::Int(1))));
+        let snbt = crate::compound_to_snbt(&map).unwrap();
+        assert_eq!(map, crate::compound_from_snbt(&snbt).unwrap());
+        assert!(crate::compound_from_snbt("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn convert_at_query() {
+        let mut map = make_map(
+            r#"{"Level": {"Entities": [{"Pos": 1}, {"Pos": 2}, {"Other": 3}], "Pos": 4}}"#,
+        );
+        let mut typ = DynamicDataType::new("Increment");
+        typ.add_structure_converter(
+            1,
+            dynamic_data_converter_func(|data, _from_version, _to_version| {
+                if let JValue::Int(i) = data {
+                    *i += 10;
+                }
+            }),
+        );
+        crate::convert_at_query(&typ, &mut map, "Level/Entities/*/Pos", 0.into(), 1.into());
+        crate::convert_at_query(&typ, &mut map, "Level/Pos/*", 0.into(), 1.into());
+        let Some(JValue::Compound(level)) = map.get("Level") else {
+            panic!("Level was not a compound");
+        };
+        assert!(matches!(level.get("Pos"), Some(JValue::Int(4))));
+        let Some(JValue::List(JList::Compound(entities))) = level.get("Entities") else {
+            panic!("Entities was not a compound list");
+        };
+        assert!(matches!(entities[0].get("Pos"), Some(JValue::Int(11))));
+        assert!(matches!(entities[1].get("Pos"), Some(JValue::Int(12))));
+        assert!(matches!(entities[2].get("Other"), Some(JValue::Int(3))));
+    }
+
+    #[test]
+    fn assert_compound_eq_diff() {
+        let expected = make_map(r#"{"same": 1, "changed": 2, "missing": 3, "nested": {"a": 4}}"#);
+        let actual = make_map(r#"{"same": 1, "changed": 5, "nested": {"a": 6}, "extra": 7}"#);
+        assert_compound_eq(&expected, &expected);
+        let message = std::panic::catch_unwind(|| assert_compound_eq(&actual, &expected))
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert!(message.contains("changed changed: expected Int(2), got Int(5)"));
+        assert!(message.contains("missing missing: Int(3)"));
+        assert!(message.contains("changed nested/a: expected Int(4), got Int(6)"));
+        assert!(message.contains("unexpected extra: Int(7)"));
+        assert!(!message.contains("same"));
     }
 
     fn simple_converted_type() -> MapDataType<'static> {
@@ -0,0 +1,93 @@
+use crate::utils::push_homogeneous;
+use crate::{string_from_java, JCompound, JList, JValue, Result};
+use java_string::JavaString;
+use quartz_nbt::{NbtCompound, NbtList, NbtTag};
+
+pub fn quartz_to_java(compound: NbtCompound) -> JCompound {
+    let mut result = JCompound::with_capacity(compound.len());
+    for (key, value) in compound {
+        result.insert(JavaString::from(key), quartz_tag_to_java(value));
+    }
+    result
+}
+
+fn quartz_tag_to_java(tag: NbtTag) -> JValue {
+    match tag {
+        NbtTag::Byte(v) => JValue::Byte(v),
+        NbtTag::Short(v) => JValue::Short(v),
+        NbtTag::Int(v) => JValue::Int(v),
+        NbtTag::Long(v) => JValue::Long(v),
+        NbtTag::Float(v) => JValue::Float(v),
+        NbtTag::Double(v) => JValue::Double(v),
+        NbtTag::ByteArray(v) => JValue::ByteArray(v),
+        NbtTag::String(v) => JValue::String(JavaString::from(v)),
+        NbtTag::List(v) => JValue::List(quartz_list_to_java(v)),
+        NbtTag::Compound(v) => JValue::Compound(quartz_to_java(v)),
+        NbtTag::IntArray(v) => JValue::IntArray(v),
+        NbtTag::LongArray(v) => JValue::LongArray(v),
+    }
+}
+
+/// quartz_nbt lists may contain mixed tag types, elements which don't match the type of the first
+/// element are dropped.
+fn quartz_list_to_java(list: NbtList) -> JList {
+    let mut result = JList::new();
+    let mut dropped = 0;
+    for (index, tag) in list.into_iter().enumerate() {
+        push_homogeneous(&mut result, quartz_tag_to_java(tag), index, &mut dropped);
+    }
+    result
+}
+
+/// Fails if any string contains unpaired surrogates, which quartz_nbt can't represent.
+pub fn java_to_quartz(compound: JCompound) -> Result<NbtCompound> {
+    let mut result = NbtCompound::with_capacity(compound.len());
+    for (key, value) in compound {
+        result.insert(string_from_java(&key)?, java_value_to_quartz(value)?);
+    }
+    Ok(result)
+}
+
+fn java_value_to_quartz(value: JValue) -> Result<NbtTag> {
+    Ok(match value {
+        JValue::Byte(v) => NbtTag::Byte(v),
+        JValue::Short(v) => NbtTag::Short(v),
+        JValue::Int(v) => NbtTag::Int(v),
+        JValue::Long(v) => NbtTag::Long(v),
+        JValue::Float(v) => NbtTag::Float(v),
+        JValue::Double(v) => NbtTag::Double(v),
+        JValue::ByteArray(v) => NbtTag::ByteArray(v),
+        JValue::String(v) => NbtTag::String(string_from_java(&v)?),
+        JValue::List(v) => NbtTag::List(java_list_to_quartz(v)?),
+        JValue::Compound(v) => NbtTag::Compound(java_to_quartz(v)?),
+        JValue::IntArray(v) => NbtTag::IntArray(v),
+        JValue::LongArray(v) => NbtTag::LongArray(v),
+    })
+}
+
+fn java_list_to_quartz(list: JList) -> Result<NbtList> {
+    Ok(match list {
+        JList::End => NbtList::new(),
+        JList::Byte(v) => v.into_iter().map(NbtTag::Byte).collect(),
+        JList::Short(v) => v.into_iter().map(NbtTag::Short).collect(),
+        JList::Int(v) => v.into_iter().map(NbtTag::Int).collect(),
+        JList::Long(v) => v.into_iter().map(NbtTag::Long).collect(),
+        JList::Float(v) => v.into_iter().map(NbtTag::Float).collect(),
+        JList::Double(v) => v.into_iter().map(NbtTag::Double).collect(),
+        JList::ByteArray(v) => v.into_iter().map(NbtTag::ByteArray).collect(),
+        JList::String(v) => v
+            .iter()
+            .map(|string| Ok(NbtTag::String(string_from_java(string)?)))
+            .collect::<Result<_>>()?,
+        JList::List(v) => v
+            .into_iter()
+            .map(|list| Ok(NbtTag::List(java_list_to_quartz(list)?)))
+            .collect::<Result<_>>()?,
+        JList::Compound(v) => v
+            .into_iter()
+            .map(|compound| Ok(NbtTag::Compound(java_to_quartz(compound)?)))
+            .collect::<Result<_>>()?,
+        JList::IntArray(v) => v.into_iter().map(NbtTag::IntArray).collect(),
+        JList::LongArray(v) => v.into_iter().map(NbtTag::LongArray).collect(),
+    })
+}
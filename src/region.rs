@@ -0,0 +1,52 @@
+use crate::{
+    java_to_quartz, quartz_to_java, AbstractMapDataType, ConversionError, DataVersion, Result,
+};
+use quartz_nbt::io::{Flavor, NbtIoError};
+
+const GZIP: u8 = 1;
+const ZLIB: u8 = 2;
+const UNCOMPRESSED: u8 = 3;
+
+/// Converts a chunk payload from a region file, i.e. the compression type byte followed by the
+/// compressed NBT, without the length prefix. The chunk is converted from its own `DataVersion` to
+/// `to_version` with [`AbstractMapDataType::convert_self_versioned`], so chunks without a valid
+/// `DataVersion` are re-serialized unchanged. The result is compressed the same way as the input.
+///
+/// Gzip (1), zlib (2) and uncompressed (3) payloads are supported. Chunks stored in external
+/// `.mcc` files can't be read from the payload alone, and are an error.
+pub fn convert_region_chunk(
+    data_type: &(impl AbstractMapDataType + ?Sized),
+    chunk: &[u8],
+    to_version: DataVersion,
+) -> Result<Vec<u8>> {
+    let Some((&compression, mut payload)) = chunk.split_first() else {
+        return Err(ConversionError {
+            message: "Empty region chunk".to_owned(),
+        });
+    };
+    let flavor = match compression {
+        GZIP => Flavor::GzCompressed,
+        ZLIB => Flavor::ZlibCompressed,
+        UNCOMPRESSED => Flavor::Uncompressed,
+        _ => {
+            return Err(ConversionError {
+                message: format!("Unsupported region chunk compression type {compression}"),
+            })
+        }
+    };
+
+    let (root, root_name) = quartz_nbt::io::read_nbt(&mut payload, flavor).map_err(io_error)?;
+    let mut data = quartz_to_java(root);
+    data_type.convert_self_versioned(&mut data, to_version);
+    let root = java_to_quartz(data)?;
+
+    let mut result = vec![compression];
+    quartz_nbt::io::write_nbt(&mut result, Some(&root_name), &root, flavor).map_err(io_error)?;
+    Ok(result)
+}
+
+fn io_error(err: NbtIoError) -> ConversionError {
+    ConversionError {
+        message: format!("Failed to read or write region chunk: {err}"),
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{nbt_type, JCompound, NbtType};
+use java_string::JavaString;
+use std::fmt::{Display, Formatter};
+
+/// The fields expected in a compound at some version, used to check data before or after
+/// converting it. Fields which aren't mentioned in the schema are allowed.
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    fields: Vec<SchemaField>,
+}
+
+#[derive(Clone, Debug)]
+struct SchemaField {
+    key: JavaString,
+    typ: NbtType,
+    required: bool,
+}
+
+impl Schema {
+    pub fn builder() -> SchemaBuilder {
+        SchemaBuilder {
+            schema: Schema::default(),
+        }
+    }
+
+    /// Checks that `data` has each required field, and that each field in the schema that it has
+    /// is of the expected type. All mismatches are reported, in the order the fields were added to
+    /// the schema.
+    pub fn validate(&self, data: &JCompound) -> Result<(), Vec<SchemaError>> {
+        let mut errors = Vec::new();
+        for field in &self.fields {
+            match data.get(&field.key[..]) {
+                Some(value) => {
+                    let actual = nbt_type(value);
+                    if actual != field.typ {
+                        errors.push(SchemaError {
+                            key: field.key.clone(),
+                            kind: SchemaErrorKind::WrongType {
+                                expected: field.typ,
+                                actual,
+                            },
+                        });
+                    }
+                }
+                None if field.required => errors.push(SchemaError {
+                    key: field.key.clone(),
+                    kind: SchemaErrorKind::Missing,
+                }),
+                None => {}
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+pub struct SchemaBuilder {
+    schema: Schema,
+}
+
+impl SchemaBuilder {
+    /// Expects `key` to exist with type `typ`.
+    pub fn required(mut self, key: impl Into<JavaString>, typ: NbtType) -> Self {
+        self.schema.fields.push(SchemaField {
+            key: key.into(),
+            typ,
+            required: true,
+        });
+        self
+    }
+
+    /// Expects `key` to have type `typ` if it exists.
+    pub fn optional(mut self, key: impl Into<JavaString>, typ: NbtType) -> Self {
+        self.schema.fields.push(SchemaField {
+            key: key.into(),
+            typ,
+            required: false,
+        });
+        self
+    }
+
+    pub fn build(self) -> Schema {
+        self.schema
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaError {
+    pub key: JavaString,
+    pub kind: SchemaErrorKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SchemaErrorKind {
+    Missing,
+    WrongType { expected: NbtType, actual: NbtType },
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            SchemaErrorKind::Missing => write!(f, "missing field {}", self.key),
+            SchemaErrorKind::WrongType { expected, actual } => write!(
+                f,
+                "field {} has type {actual}, expected {expected}",
+                self.key
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
@@ -0,0 +1,109 @@
+use crate::{JList, JValue};
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum NbtType {
+    End,
+    Byte,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    ByteArray,
+    String,
+    List,
+    Compound,
+    IntArray,
+    LongArray,
+}
+
+impl NbtType {
+    pub fn name(self) -> &'static str {
+        match self {
+            NbtType::End => "End",
+            NbtType::Byte => "Byte",
+            NbtType::Short => "Short",
+            NbtType::Int => "Int",
+            NbtType::Long => "Long",
+            NbtType::Float => "Float",
+            NbtType::Double => "Double",
+            NbtType::ByteArray => "ByteArray",
+            NbtType::String => "String",
+            NbtType::List => "List",
+            NbtType::Compound => "Compound",
+            NbtType::IntArray => "IntArray",
+            NbtType::LongArray => "LongArray",
+        }
+    }
+}
+
+impl Display for NbtType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum NumberKind {
+    Byte,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+}
+
+impl NumberKind {
+    pub fn nbt_type(self) -> NbtType {
+        match self {
+            NumberKind::Byte => NbtType::Byte,
+            NumberKind::Short => NbtType::Short,
+            NumberKind::Int => NbtType::Int,
+            NumberKind::Long => NbtType::Long,
+            NumberKind::Float => NbtType::Float,
+            NumberKind::Double => NbtType::Double,
+        }
+    }
+}
+
+impl Display for NumberKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.nbt_type(), f)
+    }
+}
+
+pub fn nbt_type(value: &JValue) -> NbtType {
+    match value {
+        JValue::Byte(_) => NbtType::Byte,
+        JValue::Short(_) => NbtType::Short,
+        JValue::Int(_) => NbtType::Int,
+        JValue::Long(_) => NbtType::Long,
+        JValue::Float(_) => NbtType::Float,
+        JValue::Double(_) => NbtType::Double,
+        JValue::ByteArray(_) => NbtType::ByteArray,
+        JValue::String(_) => NbtType::String,
+        JValue::List(_) => NbtType::List,
+        JValue::Compound(_) => NbtType::Compound,
+        JValue::IntArray(_) => NbtType::IntArray,
+        JValue::LongArray(_) => NbtType::LongArray,
+    }
+}
+
+pub fn list_element_type(list: &JList) -> NbtType {
+    match list {
+        JList::End => NbtType::End,
+        JList::Byte(_) => NbtType::Byte,
+        JList::Short(_) => NbtType::Short,
+        JList::Int(_) => NbtType::Int,
+        JList::Long(_) => NbtType::Long,
+        JList::Float(_) => NbtType::Float,
+        JList::Double(_) => NbtType::Double,
+        JList::ByteArray(_) => NbtType::ByteArray,
+        JList::String(_) => NbtType::String,
+        JList::List(_) => NbtType::List,
+        JList::Compound(_) => NbtType::Compound,
+        JList::IntArray(_) => NbtType::IntArray,
+        JList::LongArray(_) => NbtType::LongArray,
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{JCompound, JValue};
+use std::fmt::Write;
+
+/// Panics with a key-by-key diff if `actual` and `expected` aren't equal. Nested compounds are
+/// diffed recursively, and their keys are reported as `/`-separated paths.
+#[track_caller]
+pub fn assert_compound_eq(actual: &JCompound, expected: &JCompound) {
+    let mut diff = String::new();
+    diff_compounds(actual, expected, "", &mut diff);
+    if !diff.is_empty() {
+        panic!("compounds are not equal:{diff}");
+    }
+}
+
+fn diff_compounds(actual: &JCompound, expected: &JCompound, path: &str, diff: &mut String) {
+    for (key, expected_value) in expected {
+        let key_path = format!("{path}{key}");
+        match actual.get(&key[..]) {
+            None => write!(diff, "\n  missing {key_path}: {expected_value:?}").unwrap(),
+            Some(JValue::Compound(actual_value)) => {
+                if let JValue::Compound(expected_value) = expected_value {
+                    diff_compounds(actual_value, expected_value, &format!("{key_path}/"), diff);
+                } else {
+                    write!(
+                        diff,
+                        "\n  changed {key_path}: expected {expected_value:?}, got {actual_value:?}"
+                    )
+                    .unwrap();
+                }
+            }
+            Some(actual_value) => {
+                if actual_value != expected_value {
+                    write!(
+                        diff,
+                        "\n  changed {key_path}: expected {expected_value:?}, got {actual_value:?}"
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+    for (key, actual_value) in actual {
+        if !expected.contains_key(&key[..]) {
+            write!(diff, "\n  unexpected {path}{key}: {actual_value:?}").unwrap();
+        }
+    }
+}
@@ -1,9 +1,12 @@
 use crate::{
-    AbstractDynamicDataType, AbstractMapDataType, AbstractValueDataType, DataVersion, JCompound,
-    JList, JValue, MapDataWalker,
+    list_element_type, nbt_type, AbstractDynamicDataType, AbstractMapDataType,
+    AbstractValueDataType, ContextualMapDataWalker, DataVersion, IdDataType, JCompound, JList,
+    JValue, JValueRef, MapDataWalker, NbtType, NumberKind, WalkContext,
 };
 use java_string::{JavaStr, JavaString};
 use log::warn;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 
 pub struct DataWalkerObjectListPaths<T>
 where
@@ -96,7 +99,8 @@ where
 {
     fn walk(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion) {
         for path in &self.paths {
-            convert_dynamic_list_in_map(&self.typ, data, path, from_version, to_version);
+            // walkers can't report the count, and the dropped elements are already logged
+            let _ = convert_dynamic_list_in_map(&self.typ, data, path, from_version, to_version);
         }
     }
 }
@@ -197,6 +201,82 @@ where
     }
 }
 
+pub struct DataWalkerContextualPaths<W>
+where
+    W: ContextualMapDataWalker,
+{
+    walker: W,
+    paths: Vec<String>,
+}
+
+impl<W> DataWalkerContextualPaths<W>
+where
+    W: ContextualMapDataWalker,
+{
+    pub fn new(walker: W, path: impl Into<String>) -> Self {
+        Self::new_multi(walker, vec![path.into()])
+    }
+
+    pub fn new_multi(walker: W, paths: Vec<String>) -> Self {
+        Self { walker, paths }
+    }
+}
+
+impl<W> MapDataWalker for DataWalkerContextualPaths<W>
+where
+    W: ContextualMapDataWalker,
+{
+    fn walk(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion) {
+        for path in &self.paths {
+            walk_map_in_map_with_context(&self.walker, data, path, from_version, to_version);
+        }
+    }
+}
+
+/// Renames the keys of the compounds at the given paths, but only when the conversion crosses
+/// `version`, i.e. when `from_version < version <= to_version`.
+pub struct DataWalkerKeyRenamer<F>
+where
+    F: Fn(&JavaStr) -> Option<JavaString>,
+{
+    version: DataVersion,
+    renamer: F,
+    paths: Vec<String>,
+}
+
+impl<F> DataWalkerKeyRenamer<F>
+where
+    F: Fn(&JavaStr) -> Option<JavaString>,
+{
+    pub fn new(version: impl Into<DataVersion>, renamer: F, path: impl Into<String>) -> Self {
+        Self::new_multi(version, renamer, vec![path.into()])
+    }
+
+    pub fn new_multi(version: impl Into<DataVersion>, renamer: F, paths: Vec<String>) -> Self {
+        Self {
+            version: version.into(),
+            renamer,
+            paths,
+        }
+    }
+}
+
+impl<F> MapDataWalker for DataWalkerKeyRenamer<F>
+where
+    F: Fn(&JavaStr) -> Option<JavaString>,
+{
+    fn walk(&self, data: &mut JCompound, from_version: DataVersion, to_version: DataVersion) {
+        if from_version >= self.version || self.version > to_version {
+            return;
+        }
+        for path in &self.paths {
+            if let Some(JValue::Compound(map)) = data.get_mut(&path[..]) {
+                rename_keys(map, &self.renamer);
+            }
+        }
+    }
+}
+
 pub fn convert_map_in_map<T>(
     data_type: T,
     data: &mut JCompound,
@@ -211,6 +291,50 @@ pub fn convert_map_in_map<T>(
     }
 }
 
+/// Like [`convert_map_in_map`], but the key of the child is computed from `data` by
+/// `key_source`. Nothing is converted if no key is computed or the child isn't a compound.
+pub fn convert_map_in_map_dynamic<T>(
+    data_type: T,
+    data: &mut JCompound,
+    key_source: impl Fn(&JCompound) -> Option<JavaString>,
+    from_version: DataVersion,
+    to_version: DataVersion,
+) where
+    T: AbstractMapDataType,
+{
+    if let Some(key) = key_source(data) {
+        convert_map_in_map(data_type, data, &key, from_version, to_version);
+    }
+}
+
+pub fn walk_map_in_map_with_context<W>(
+    walker: W,
+    data: &mut JCompound,
+    path: &(impl AsRef<JavaStr> + ?Sized),
+    from_version: DataVersion,
+    to_version: DataVersion,
+) where
+    W: ContextualMapDataWalker,
+{
+    let key = path.as_ref();
+    let Some(JValue::Compound(map)) = data.get_mut(key) else {
+        return;
+    };
+    let mut map = std::mem::take(map);
+    walker.walk(
+        &mut map,
+        &WalkContext { parent: data, key },
+        from_version,
+        to_version,
+    );
+    if let Some(JValue::Compound(slot)) = data.get_mut(key) {
+        *slot = map;
+    }
+}
+
+/// Lists of any type other than compounds are left unchanged. Empty lists, including the untyped
+/// `End` list which an empty list of any type may be stored as, are left unchanged by this and all
+/// the other list helpers.
 pub fn convert_map_list_in_map<T>(
     data_type: T,
     data: &mut JCompound,
@@ -229,6 +353,72 @@ pub fn convert_map_list_in_map<T>(
     }
 }
 
+/// Converts the compounds `depth` list levels below `path`, e.g. a depth of 2 converts the
+/// compounds in a list of lists of compounds. A depth of 1 is the same as
+/// [`convert_map_list_in_map`], and a depth of 0 converts the compound at `path` itself. Branches
+/// which end early or hold something other than lists or compounds are skipped.
+pub fn convert_map_in_nested_lists<T>(
+    data_type: T,
+    data: &mut JCompound,
+    path: &(impl AsRef<JavaStr> + ?Sized),
+    depth: usize,
+    from_version: DataVersion,
+    to_version: DataVersion,
+) where
+    T: AbstractMapDataType,
+{
+    fn convert_nested<T: AbstractMapDataType>(
+        data_type: &T,
+        list: &mut JList,
+        depth: usize,
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) {
+        match list {
+            JList::Compound(maps) if depth == 1 => {
+                for map in maps {
+                    data_type.convert(map, from_version, to_version);
+                }
+            }
+            JList::List(lists) if depth > 1 => {
+                for list in lists {
+                    convert_nested(data_type, list, depth - 1, from_version, to_version);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match data.get_mut(path.as_ref()) {
+        Some(JValue::Compound(map)) if depth == 0 => {
+            data_type.convert(map, from_version, to_version);
+        }
+        Some(JValue::List(list)) if depth > 0 => {
+            convert_nested(&data_type, list, depth, from_version, to_version);
+        }
+        _ => {}
+    }
+}
+
+/// Converts each compound in the list at `path` with [`IdDataType::convert_split`], replacing it
+/// with the compounds it was split into.
+pub fn convert_split_list_in_map(
+    data_type: &IdDataType,
+    data: &mut JCompound,
+    path: &(impl AsRef<JavaStr> + ?Sized),
+    from_version: DataVersion,
+    to_version: DataVersion,
+) {
+    if let Some(valence_nbt::Value::List(valence_nbt::List::Compound(list))) =
+        data.get_mut(path.as_ref())
+    {
+        *list = std::mem::take(list)
+            .into_iter()
+            .flat_map(|map| data_type.convert_split(map, from_version, to_version))
+            .collect();
+    }
+}
+
 pub fn convert_object_in_map<T>(
     data_type: T,
     data: &mut JCompound,
@@ -272,6 +462,26 @@ pub fn convert_object_list_in_map<T>(
     }
 }
 
+/// Converts each element if the value at `path` is a list, or the value itself otherwise. Only
+/// the outer list is unwrapped: elements which are themselves lists are converted as a whole.
+pub fn convert_object_or_list_in_map<T>(
+    data_type: T,
+    data: &mut JCompound,
+    path: &(impl AsRef<JavaStr> + ?Sized),
+    from_version: DataVersion,
+    to_version: DataVersion,
+) where
+    T: AbstractValueDataType,
+{
+    match data.get_mut(path.as_ref()) {
+        Some(valence_nbt::Value::List(list)) => {
+            convert_object_list(data_type, list, from_version, to_version)
+        }
+        Some(obj) => data_type.convert(&mut obj.as_value_mut(), from_version, to_version),
+        None => {}
+    }
+}
+
 pub fn convert_dynamic_in_map<T>(
     data_type: T,
     data: &mut JCompound,
@@ -286,152 +496,1771 @@ pub fn convert_dynamic_in_map<T>(
     }
 }
 
+/// Returns the number of elements which were dropped because their converted type didn't match
+/// the rest of the list. To keep the original list when that would happen, use
+/// [`convert_dynamic_list_in_map_with_policy`].
+#[must_use = "elements of a mismatched type are dropped, which the count reports"]
 pub fn convert_dynamic_list_in_map<T>(
     data_type: T,
     data: &mut JCompound,
     path: &(impl AsRef<JavaStr> + ?Sized),
     from_version: DataVersion,
     to_version: DataVersion,
-) where
+) -> usize
+where
+    T: AbstractDynamicDataType,
+{
+    match data.get_mut(path.as_ref()) {
+        Some(valence_nbt::Value::List(list)) => {
+            convert_dynamic_list(data_type, list, from_version, to_version)
+        }
+        _ => 0,
+    }
+}
+
+/// Returns the number of elements which were dropped because their converted type didn't match
+/// the rest of the list.
+#[must_use = "elements of a mismatched type are dropped, which the count reports"]
+pub fn convert_dynamic_list<T>(
+    data_type: T,
+    list: &mut JList,
+    from_version: DataVersion,
+    to_version: DataVersion,
+) -> usize
+where
+    T: AbstractDynamicDataType,
+{
+    convert_dynamic_list_buffered(data_type, list, from_version, to_version, &mut JList::new())
+}
+
+/// Like [`convert_dynamic_list_in_map`], but rebuilds the list in `scratch` if an element changes
+/// type, rather than in a fresh list. The old list's storage is then cleared and left in
+/// `scratch`, so passing the same scratch list to repeated conversions reuses its capacity
+/// whenever a rebuilt list has the same element type as the list replaced by the previous rebuild,
+/// e.g. when elements of another type are dropped. `scratch` is always left empty.
+pub fn convert_dynamic_list_in_map_buffered<T>(
+    data_type: T,
+    data: &mut JCompound,
+    path: &(impl AsRef<JavaStr> + ?Sized),
+    from_version: DataVersion,
+    to_version: DataVersion,
+    scratch: &mut JList,
+) -> usize
+where
+    T: AbstractDynamicDataType,
+{
+    match data.get_mut(path.as_ref()) {
+        Some(valence_nbt::Value::List(list)) => {
+            convert_dynamic_list_buffered(data_type, list, from_version, to_version, scratch)
+        }
+        _ => 0,
+    }
+}
+
+/// See [`convert_dynamic_list_in_map_buffered`].
+pub fn convert_dynamic_list_buffered<T>(
+    data_type: T,
+    list: &mut JList,
+    from_version: DataVersion,
+    to_version: DataVersion,
+    scratch: &mut JList,
+) -> usize
+where
     T: AbstractDynamicDataType,
 {
-    fn convert_list_inner<T: AbstractDynamicDataType, E: Into<JValue>>(
+    fn convert_list_inner<T: AbstractDynamicDataType, E: ListElement>(
         data_type: T,
         in_list: &mut Vec<E>,
         from_version: DataVersion,
         to_version: DataVersion,
-    ) -> JList {
-        let mut result = JList::new();
-        let mut all_success = true;
-        for element in in_list.drain(..) {
-            let mut element = element.into();
+        result: &mut JList,
+    ) -> Option<usize> {
+        for index in 0..in_list.len() {
+            let mut element: JValue =
+                std::mem::replace(&mut in_list[index], E::placeholder()).into();
             data_type.convert(&mut element, from_version, to_version);
-            all_success &= result.try_push(element)
-        }
-        if !all_success {
-            warn!("Result of list conversion was not homogenous");
+            match E::from_value(element) {
+                Ok(element) => in_list[index] = element,
+                Err(element) => {
+                    // the element changed type, fall back to rebuilding the list
+                    let mut dropped = 0;
+                    for (i, converted) in in_list.drain(..index).enumerate() {
+                        push_homogeneous(result, converted.into(), i, &mut dropped);
+                    }
+                    push_homogeneous(result, element, index, &mut dropped);
+                    for (i, element) in in_list.drain(1..).enumerate() {
+                        let mut element: JValue = element.into();
+                        data_type.convert(&mut element, from_version, to_version);
+                        push_homogeneous(result, element, index + 1 + i, &mut dropped);
+                    }
+                    return Some(dropped);
+                }
+            }
         }
-        result
+        None
     }
 
-    let Some(valence_nbt::Value::List(list)) = data.get_mut(path.as_ref()) else {
-        return;
-    };
-    *list = match list {
-        valence_nbt::List::End => JList::End,
+    clear_list(scratch);
+    let dropped = match list {
+        valence_nbt::List::End => None,
         valence_nbt::List::Byte(bytes) => {
-            convert_list_inner(data_type, bytes, from_version, to_version)
+            convert_list_inner(data_type, bytes, from_version, to_version, scratch)
         }
         valence_nbt::List::Short(shorts) => {
-            convert_list_inner(data_type, shorts, from_version, to_version)
+            convert_list_inner(data_type, shorts, from_version, to_version, scratch)
         }
         valence_nbt::List::Int(ints) => {
-            convert_list_inner(data_type, ints, from_version, to_version)
+            convert_list_inner(data_type, ints, from_version, to_version, scratch)
         }
         valence_nbt::List::Long(longs) => {
-            convert_list_inner(data_type, longs, from_version, to_version)
+            convert_list_inner(data_type, longs, from_version, to_version, scratch)
         }
         valence_nbt::List::Float(floats) => {
-            convert_list_inner(data_type, floats, from_version, to_version)
+            convert_list_inner(data_type, floats, from_version, to_version, scratch)
         }
         valence_nbt::List::Double(doubles) => {
-            convert_list_inner(data_type, doubles, from_version, to_version)
+            convert_list_inner(data_type, doubles, from_version, to_version, scratch)
         }
         valence_nbt::List::ByteArray(byte_arrays) => {
-            convert_list_inner(data_type, byte_arrays, from_version, to_version)
+            convert_list_inner(data_type, byte_arrays, from_version, to_version, scratch)
         }
         valence_nbt::List::String(strings) => {
-            convert_list_inner(data_type, strings, from_version, to_version)
+            convert_list_inner(data_type, strings, from_version, to_version, scratch)
         }
         valence_nbt::List::List(lists) => {
-            convert_list_inner(data_type, lists, from_version, to_version)
+            convert_list_inner(data_type, lists, from_version, to_version, scratch)
         }
         valence_nbt::List::Compound(compounds) => {
-            convert_list_inner(data_type, compounds, from_version, to_version)
+            convert_list_inner(data_type, compounds, from_version, to_version, scratch)
         }
         valence_nbt::List::IntArray(int_arrays) => {
-            convert_list_inner(data_type, int_arrays, from_version, to_version)
+            convert_list_inner(data_type, int_arrays, from_version, to_version, scratch)
         }
         valence_nbt::List::LongArray(long_arrays) => {
-            convert_list_inner(data_type, long_arrays, from_version, to_version)
+            convert_list_inner(data_type, long_arrays, from_version, to_version, scratch)
+        }
+    };
+    match dropped {
+        Some(dropped) => {
+            std::mem::swap(list, scratch);
+            clear_list(scratch);
+            dropped
         }
+        None => 0,
     }
 }
 
-pub fn convert_values_in_map<T>(
+/// Empties a list, keeping its element type and capacity.
+fn clear_list(list: &mut JList) {
+    match list {
+        JList::End => {}
+        JList::Byte(bytes) => bytes.clear(),
+        JList::Short(shorts) => shorts.clear(),
+        JList::Int(ints) => ints.clear(),
+        JList::Long(longs) => longs.clear(),
+        JList::Float(floats) => floats.clear(),
+        JList::Double(doubles) => doubles.clear(),
+        JList::ByteArray(byte_arrays) => byte_arrays.clear(),
+        JList::String(strings) => strings.clear(),
+        JList::List(lists) => lists.clear(),
+        JList::Compound(compounds) => compounds.clear(),
+        JList::IntArray(int_arrays) => int_arrays.clear(),
+        JList::LongArray(long_arrays) => long_arrays.clear(),
+    }
+}
+
+/// What happens when converting a list would change the types of some of its elements to a type
+/// different from the rest of the list, which a list can't hold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListConversionPolicy {
+    /// Elements whose converted type doesn't match the first element are dropped with a warning.
+    #[default]
+    DropMismatched,
+    /// The original list is kept unconverted and a warning is logged. This needs a copy of the
+    /// list to convert, so is slower than dropping.
+    KeepOriginal,
+}
+
+/// Like [`convert_dynamic_list_in_map`], but with a choice of what to do with elements whose
+/// converted type doesn't match the rest of the list. Returns the number of such elements, which
+/// were dropped or caused the list to be kept unconverted.
+pub fn convert_dynamic_list_in_map_with_policy<T>(
     data_type: T,
     data: &mut JCompound,
     path: &(impl AsRef<JavaStr> + ?Sized),
     from_version: DataVersion,
     to_version: DataVersion,
-) where
-    T: AbstractMapDataType,
+    policy: ListConversionPolicy,
+) -> usize
+where
+    T: AbstractDynamicDataType,
 {
-    if let Some(valence_nbt::Value::Compound(map)) = data.get_mut(path.as_ref()) {
-        convert_values(data_type, map, from_version, to_version);
+    match data.get_mut(path.as_ref()) {
+        Some(valence_nbt::Value::List(list)) => {
+            convert_dynamic_list_with_policy(data_type, list, from_version, to_version, policy)
+        }
+        _ => 0,
     }
 }
 
-pub fn convert_values<T>(
+/// See [`convert_dynamic_list_in_map_with_policy`].
+pub fn convert_dynamic_list_with_policy<T>(
+    data_type: T,
+    list: &mut JList,
+    from_version: DataVersion,
+    to_version: DataVersion,
+    policy: ListConversionPolicy,
+) -> usize
+where
+    T: AbstractDynamicDataType,
+{
+    if policy == ListConversionPolicy::DropMismatched {
+        return convert_dynamic_list(data_type, list, from_version, to_version);
+    }
+
+    let mut values = list_into_values(list.clone());
+    for value in &mut values {
+        data_type.convert(value, from_version, to_version);
+    }
+    let Some(first_type) = values.first().map(nbt_type) else {
+        return 0;
+    };
+    let mismatched = values
+        .iter()
+        .filter(|value| nbt_type(value) != first_type)
+        .count();
+    if mismatched != 0 {
+        warn!(
+            "Result of list conversion was not homogenous: keeping the original list, as {mismatched} elements did not have type {first_type}"
+        );
+        return mismatched;
+    }
+
+    let mut result = JList::new();
+    for value in values {
+        result.try_push(value);
+    }
+    *list = result;
+    0
+}
+
+/// Converts each entry of the palette list at `palette_key` once. Indices into the palette stay
+/// valid since the palette entries are converted in place, so the (usually much larger) index data
+/// doesn't need to be touched. If any entry's converted type doesn't match the rest of the palette,
+/// dropping it would shift later indices, so the palette is left unconverted instead. Returns
+/// whether the palette was converted.
+pub fn convert_palette_and_indices<T>(
+    data_type: T,
+    data: &mut JCompound,
+    palette_key: &(impl AsRef<JavaStr> + ?Sized),
+    from_version: DataVersion,
+    to_version: DataVersion,
+) -> bool
+where
+    T: AbstractDynamicDataType,
+{
+    convert_dynamic_list_in_map_with_policy(
+        data_type,
+        data,
+        palette_key,
+        from_version,
+        to_version,
+        ListConversionPolicy::KeepOriginal,
+    ) == 0
+}
+
+/// Converts every value matched by `query`, a `/`-separated list of keys where `*` matches each
+/// element of a list or each value of a compound. Values which don't have the type needed to
+/// continue matching the query are silently skipped.
+pub fn convert_at_query<T>(
     data_type: T,
     data: &mut JCompound,
+    query: &str,
     from_version: DataVersion,
     to_version: DataVersion,
 ) where
-    T: AbstractMapDataType,
+    T: AbstractDynamicDataType,
 {
-    for obj in data.values_mut() {
-        if let valence_nbt::Value::Compound(map) = obj {
-            data_type.convert(map, from_version, to_version);
+    fn query_compound<T: AbstractDynamicDataType>(
+        data_type: &T,
+        data: &mut JCompound,
+        segments: &[&str],
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) {
+        let Some((&segment, rest)) = segments.split_first() else {
+            return;
+        };
+        if segment == "*" {
+            for value in data.values_mut() {
+                query_value(data_type, value, rest, from_version, to_version);
+            }
+        } else if let Some(value) = data.get_mut(segment) {
+            query_value(data_type, value, rest, from_version, to_version);
         }
     }
-}
 
-#[inline]
-pub fn rename_key(map: &mut JCompound, from: impl AsRef<JavaStr>, to: impl Into<JavaString>) {
-    if let Some(value) = map.remove(from.as_ref()) {
-        map.insert(to.into(), value);
+    fn query_list<T: AbstractDynamicDataType>(
+        data_type: &T,
+        data: &mut JList,
+        segments: &[&str],
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) {
+        let Some((&"*", rest)) = segments.split_first() else {
+            return;
+        };
+        if rest.is_empty() {
+            let _ = convert_dynamic_list(data_type, data, from_version, to_version);
+            return;
+        }
+        match data {
+            JList::Compound(compounds) => {
+                for compound in compounds {
+                    query_compound(data_type, compound, rest, from_version, to_version);
+                }
+            }
+            JList::List(lists) => {
+                for list in lists {
+                    query_list(data_type, list, rest, from_version, to_version);
+                }
+            }
+            _ => {}
+        }
     }
-}
 
-pub fn rename_keys(map: &mut JCompound, renamer: impl Fn(&JavaStr) -> Option<JavaString>) {
-    let renames: Vec<_> = map
-        .keys()
-        .filter_map(|key| renamer(key).map(|new_key| (key.clone(), new_key)))
-        .collect();
-    let renamed: Vec<_> = renames
-        .into_iter()
-        .map(|(from, to)| (to, map.remove(&from[..]).unwrap()))
-        .collect();
-    for (key, value) in renamed {
-        map.insert(key, value);
+    fn query_value<T: AbstractDynamicDataType>(
+        data_type: &T,
+        data: &mut JValue,
+        segments: &[&str],
+        from_version: DataVersion,
+        to_version: DataVersion,
+    ) {
+        if segments.is_empty() {
+            data_type.convert(data, from_version, to_version);
+            return;
+        }
+        match data {
+            JValue::Compound(compound) => {
+                query_compound(data_type, compound, segments, from_version, to_version)
+            }
+            JValue::List(list) => query_list(data_type, list, segments, from_version, to_version),
+            _ => {}
+        }
     }
+
+    let segments: Vec<&str> = query.split('/').collect();
+    query_compound(&data_type, data, &segments, from_version, to_version);
 }
 
-pub fn get_mut_multi<'a, const N: usize>(
-    map: &'a mut JCompound,
-    keys: [&str; N],
-) -> [Option<&'a mut JValue>; N] {
-    #[cold]
-    #[inline(never)]
-    fn non_unique_keys(keys: &[&str]) -> ! {
-        panic!("keys are not all unique: {keys:?}")
+pub(crate) fn push_homogeneous(
+    list: &mut JList,
+    element: JValue,
+    index: usize,
+    dropped: &mut usize,
+) {
+    let element_type = nbt_type(&element);
+    if list.is_empty() && list_element_type(list) != element_type {
+        // an emptied scratch list keeps its old element type, which doesn't restrict what it holds
+        *list = JList::new();
+    }
+    if !list.try_push(element) {
+        warn!(
+            "Result of list conversion was not homogenous: dropping element {index} of type {element_type} from list of type {}",
+            list_element_type(list)
+        );
+        *dropped += 1;
     }
+}
 
-    if N > 1 {
-        for i in 0..N - 1 {
-            for j in i + 1..N {
-                if keys[i] == keys[j] {
-                    non_unique_keys(&keys);
+trait ListElement: Into<JValue> {
+    fn placeholder() -> Self;
+    fn from_value(value: JValue) -> Result<Self, JValue>;
+}
+
+macro_rules! list_element {
+    ($ty:ty, $variant:ident, $placeholder:expr) => {
+        impl ListElement for $ty {
+            #[inline]
+            fn placeholder() -> Self {
+                $placeholder
+            }
+
+            #[inline]
+            fn from_value(value: JValue) -> Result<Self, JValue> {
+                match value {
+                    JValue::$variant(value) => Ok(value),
+                    value => Err(value),
                 }
             }
         }
-    }
+    };
+}
 
-    keys.map(|key| {
-        map.get_mut(key).map(|value| {
-            // SAFETY: we just checked that all keys are unique, so these mutable references are all different values in the map, so they can coexist.
-            unsafe { &mut *(value as *mut _) }
-        })
-    })
+list_element!(i8, Byte, 0);
+list_element!(i16, Short, 0);
+list_element!(i32, Int, 0);
+list_element!(i64, Long, 0);
+list_element!(f32, Float, 0.0);
+list_element!(f64, Double, 0.0);
+list_element!(Vec<i8>, ByteArray, Vec::new());
+list_element!(JavaString, String, JavaString::new());
+list_element!(JList, List, JList::new());
+list_element!(JCompound, Compound, JCompound::new());
+list_element!(Vec<i32>, IntArray, Vec::new());
+list_element!(Vec<i64>, LongArray, Vec::new());
+
+pub fn convert_values_in_map<T>(
+    data_type: T,
+    data: &mut JCompound,
+    path: &(impl AsRef<JavaStr> + ?Sized),
+    from_version: DataVersion,
+    to_version: DataVersion,
+) where
+    T: AbstractMapDataType,
+{
+    if let Some(valence_nbt::Value::Compound(map)) = data.get_mut(path.as_ref()) {
+        convert_values(data_type, map, from_version, to_version);
+    }
+}
+
+/// Renames the keys of the compound at `path` with `key_fn` and converts its compound values with
+/// `data_type`, in a single pass. Values are converted whether or not their key is renamed, and
+/// key collisions are resolved the same way as in [`rename_keys`]: a renamed entry replaces any
+/// entry already at its new key.
+pub fn convert_map_entries<T>(
+    data: &mut JCompound,
+    path: &(impl AsRef<JavaStr> + ?Sized),
+    key_fn: impl Fn(&JavaStr) -> Option<JavaString>,
+    data_type: T,
+    from_version: DataVersion,
+    to_version: DataVersion,
+) where
+    T: AbstractMapDataType,
+{
+    let Some(valence_nbt::Value::Compound(entries)) = data.get_mut(path.as_ref()) else {
+        return;
+    };
+
+    let mut result = JCompound::with_capacity(entries.len());
+    let mut renamed = Vec::new();
+    for (key, mut value) in std::mem::take(entries) {
+        if let valence_nbt::Value::Compound(map) = &mut value {
+            data_type.convert(map, from_version, to_version);
+        }
+        match key_fn(&key) {
+            Some(new_key) => renamed.push((new_key, value)),
+            None => {
+                result.insert(key, value);
+            }
+        }
+    }
+    for (key, value) in renamed {
+        result.insert(key, value);
+    }
+    *entries = result;
+}
+
+pub fn convert_values<T>(
+    data_type: T,
+    data: &mut JCompound,
+    from_version: DataVersion,
+    to_version: DataVersion,
+) where
+    T: AbstractMapDataType,
+{
+    for obj in data.values_mut() {
+        if let valence_nbt::Value::Compound(map) = obj {
+            data_type.convert(map, from_version, to_version);
+        }
+    }
+}
+
+/// Renames the keys of `data` with `key_renamer`, resolving collisions as in [`rename_keys`], and
+/// then converts its compound values with `data_type`. Use [`convert_map_entries`] for a compound
+/// nested at a path.
+pub fn convert_keyed_values<T>(
+    data_type: T,
+    data: &mut JCompound,
+    key_renamer: impl Fn(&JavaStr) -> Option<JavaString>,
+    from_version: DataVersion,
+    to_version: DataVersion,
+) where
+    T: AbstractMapDataType,
+{
+    rename_keys(data, key_renamer);
+    convert_values(data_type, data, from_version, to_version);
+}
+
+pub fn compound_entries_of_type(
+    map: &JCompound,
+    typ: NbtType,
+) -> impl Iterator<Item = (&JavaStr, &JValue)> {
+    map.iter()
+        .filter(move |(_, value)| nbt_type(value) == typ)
+        .map(|(key, value)| (&key[..], value))
+}
+
+pub fn compound_entries_of_type_mut(
+    map: &mut JCompound,
+    typ: NbtType,
+) -> impl Iterator<Item = (&JavaStr, &mut JValue)> {
+    map.iter_mut()
+        .filter(move |(_, value)| nbt_type(value) == typ)
+        .map(|(key, value)| (&key[..], value))
+}
+
+#[inline]
+pub fn rename_key(
+    map: &mut JCompound,
+    from: impl AsRef<JavaStr>,
+    to: impl AsRef<JavaStr> + Into<JavaString>,
+) {
+    let from = from.as_ref();
+    if from == to.as_ref() {
+        return;
+    }
+    if let Some(value) = map.remove(from) {
+        map.insert(to.into(), value);
+    }
+}
+
+/// Like [`rename_key`], but only renames if `predicate` accepts the value. Returns whether the key
+/// was renamed.
+pub fn rename_key_if(
+    map: &mut JCompound,
+    from: impl AsRef<JavaStr>,
+    to: impl AsRef<JavaStr> + Into<JavaString>,
+    predicate: impl Fn(&JValue) -> bool,
+) -> bool {
+    let from = from.as_ref();
+    if !map.get(from).is_some_and(predicate) {
+        return false;
+    }
+    rename_key(map, from, to);
+    true
+}
+
+pub fn rename_keys(map: &mut JCompound, renamer: impl Fn(&JavaStr) -> Option<JavaString>) {
+    let renames: Vec<_> = map
+        .keys()
+        .filter_map(|key| renamer(key).map(|new_key| (key.clone(), new_key)))
+        .collect();
+    let renamed: Vec<_> = renames
+        .into_iter()
+        .map(|(from, to)| (to, map.remove(&from[..]).unwrap()))
+        .collect();
+    for (key, value) in renamed {
+        map.insert(key, value);
+    }
+}
+
+/// Rewrites every key through `normalizer`. Keys that normalize to themselves are left in place,
+/// and collisions are resolved the same way as in [`rename_keys`].
+pub fn normalize_keys(map: &mut JCompound, normalizer: impl Fn(&JavaStr) -> JavaString) {
+    rename_keys(map, |key| {
+        let new_key = normalizer(key);
+        (new_key[..] != *key).then_some(new_key)
+    });
+}
+
+/// Trims surrounding whitespace from every key and lowercases its ASCII characters.
+pub fn ascii_lowercase_keys(map: &mut JCompound) {
+    normalize_keys(map, |key| key.trim().to_ascii_lowercase());
+}
+
+/// Inserts `default()` at `key` if there is nothing there. Returns whether it was inserted.
+pub fn ensure_field(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    default: impl FnOnce() -> JValue,
+) -> bool {
+    let key = key.as_ref();
+    if map.contains_key(key) {
+        return false;
+    }
+    map.insert(key.to_owned(), default());
+    true
+}
+
+/// Like [`ensure_field`], but also replaces a value which isn't of type `typ`, logging a warning.
+pub fn ensure_field_typed(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    typ: NbtType,
+    default: impl FnOnce() -> JValue,
+) -> bool {
+    let key = key.as_ref();
+    match map.get(key) {
+        Some(value) if nbt_type(value) == typ => return false,
+        Some(value) => warn!(
+            "Replacing {key} of type {} with a default value of type {typ}",
+            nbt_type(value)
+        ),
+        None => {}
+    }
+    map.insert(key.to_owned(), default());
+    true
+}
+
+/// A change to a single field of a compound, see [`migrate_field`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldAction {
+    /// Moves the value at `from` to `to`, replacing any value already there.
+    Rename {
+        from: JavaString,
+        to: JavaString,
+    },
+    Remove(JavaString),
+    /// Inserts a value, replacing any value already there.
+    Insert(JavaString, JValue),
+}
+
+/// Decides on a change with `decide`, which can read the whole compound, and then applies it.
+/// Returns whether a change was decided on.
+pub fn migrate_field(
+    map: &mut JCompound,
+    decide: impl FnOnce(&JCompound) -> Option<FieldAction>,
+) -> bool {
+    let Some(action) = decide(map) else {
+        return false;
+    };
+    match action {
+        FieldAction::Rename { from, to } => rename_key(map, from, to),
+        FieldAction::Remove(key) => {
+            map.remove(&key[..]);
+        }
+        FieldAction::Insert(key, value) => {
+            map.insert(key, value);
+        }
+    }
+    true
+}
+
+/// Deep-clones the value reached by following `path` through nested compounds, or returns `None`
+/// if the path doesn't resolve. An empty path clones the whole compound.
+pub fn clone_subtree(map: &JCompound, path: &[&str]) -> Option<JValue> {
+    let Some((last, parents)) = path.split_last() else {
+        return Some(JValue::Compound(map.clone()));
+    };
+    let mut map = map;
+    for key in parents {
+        let Some(JValue::Compound(child)) = map.get(*key) else {
+            return None;
+        };
+        map = child;
+    }
+    map.get(*last).cloned()
+}
+
+/// Returns the length of the list at `key`, or `None` if there is no list there.
+pub fn list_len(map: &JCompound, key: impl AsRef<JavaStr>) -> Option<usize> {
+    match map.get(key.as_ref()) {
+        Some(JValue::List(list)) => Some(list.len()),
+        _ => None,
+    }
+}
+
+/// Returns the length of the byte array at `key`, or `None` if there is no byte array there.
+pub fn byte_array_len(map: &JCompound, key: impl AsRef<JavaStr>) -> Option<usize> {
+    match map.get(key.as_ref()) {
+        Some(JValue::ByteArray(array)) => Some(array.len()),
+        _ => None,
+    }
+}
+
+/// Returns the length of the int array at `key`, or `None` if there is no int array there.
+pub fn int_array_len(map: &JCompound, key: impl AsRef<JavaStr>) -> Option<usize> {
+    match map.get(key.as_ref()) {
+        Some(JValue::IntArray(array)) => Some(array.len()),
+        _ => None,
+    }
+}
+
+/// Returns the length of the long array at `key`, or `None` if there is no long array there.
+pub fn long_array_len(map: &JCompound, key: impl AsRef<JavaStr>) -> Option<usize> {
+    match map.get(key.as_ref()) {
+        Some(JValue::LongArray(array)) => Some(array.len()),
+        _ => None,
+    }
+}
+
+pub fn get_mut_multi<'a, const N: usize>(
+    map: &'a mut JCompound,
+    keys: [&str; N],
+) -> [Option<&'a mut JValue>; N] {
+    #[cold]
+    #[inline(never)]
+    fn non_unique_keys(keys: &[&str]) -> ! {
+        panic!("keys are not all unique: {keys:?}")
+    }
+
+    if N > 1 {
+        for i in 0..N - 1 {
+            for j in i + 1..N {
+                if keys[i] == keys[j] {
+                    non_unique_keys(&keys);
+                }
+            }
+        }
+    }
+
+    keys.map(|key| {
+        map.get_mut(key).map(|value| {
+            // SAFETY: we just checked that all keys are unique, so these mutable references are all different values in the map, so they can coexist.
+            unsafe { &mut *(value as *mut _) }
+        })
+    })
+}
+
+pub fn wrap_in_list(map: &mut JCompound, key: impl AsRef<JavaStr>) {
+    let Some(value) = map.get_mut(key.as_ref()) else {
+        return;
+    };
+    if matches!(value, JValue::List(_)) {
+        return;
+    }
+
+    let list = match std::mem::replace(value, JValue::Byte(0)) {
+        JValue::Byte(v) => JList::Byte(vec![v]),
+        JValue::Short(v) => JList::Short(vec![v]),
+        JValue::Int(v) => JList::Int(vec![v]),
+        JValue::Long(v) => JList::Long(vec![v]),
+        JValue::Float(v) => JList::Float(vec![v]),
+        JValue::Double(v) => JList::Double(vec![v]),
+        JValue::ByteArray(v) => JList::ByteArray(vec![v]),
+        JValue::String(v) => JList::String(vec![v]),
+        JValue::List(v) => JList::List(vec![v]),
+        JValue::Compound(v) => JList::Compound(vec![v]),
+        JValue::IntArray(v) => JList::IntArray(vec![v]),
+        JValue::LongArray(v) => JList::LongArray(vec![v]),
+    };
+    *value = JValue::List(list);
+}
+
+pub fn unwrap_single_list(map: &mut JCompound, key: impl AsRef<JavaStr>, remove_if_empty: bool) {
+    let key = key.as_ref();
+    let Some(value) = map.get_mut(key) else {
+        return;
+    };
+    let JValue::List(list) = &mut *value else {
+        return;
+    };
+
+    match list.len() {
+        0 if remove_if_empty => {
+            map.remove(key);
+        }
+        1 => {
+            let list = std::mem::replace(list, JList::new());
+            if let Some(element) = list_into_values(list).pop() {
+                *value = element;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Moves `map[child_key][field_key]` to `map[dest_key]`, doing nothing if the child compound or
+/// field doesn't exist.
+pub fn move_up(
+    map: &mut JCompound,
+    child_key: impl AsRef<JavaStr>,
+    field_key: impl AsRef<JavaStr>,
+    dest_key: impl Into<JavaString>,
+    remove_empty_child: bool,
+) {
+    let child_key = child_key.as_ref();
+    let Some(JValue::Compound(child)) = map.get_mut(child_key) else {
+        return;
+    };
+    let Some(value) = child.remove(field_key.as_ref()) else {
+        return;
+    };
+    if remove_empty_child && child.is_empty() {
+        map.remove(child_key);
+    }
+    map.insert(dest_key.into(), value);
+}
+
+/// Moves `map[field_key]` to `map[child_key][field_key]`, creating the child compound if it doesn't
+/// exist. Does nothing if the field doesn't exist, or if the child exists but isn't a compound.
+pub fn move_down(
+    map: &mut JCompound,
+    field_key: impl AsRef<JavaStr>,
+    child_key: impl AsRef<JavaStr>,
+) {
+    let field_key = field_key.as_ref();
+    let child_key = child_key.as_ref();
+    if !map.contains_key(field_key) {
+        return;
+    }
+    if let Some(child) = map.get(child_key) {
+        if !matches!(child, JValue::Compound(_)) {
+            warn!(
+                "Could not move {field_key} into {child_key} of type {}",
+                nbt_type(child)
+            );
+            return;
+        }
+    }
+
+    let Some(value) = map.remove(field_key) else {
+        return;
+    };
+    if !map.contains_key(child_key) {
+        map.insert(child_key.to_owned(), JCompound::new());
+    }
+    if let Some(JValue::Compound(child)) = map.get_mut(child_key) {
+        child.insert(field_key.to_owned(), value);
+    }
+}
+
+/// Replaces `map[key]` with `map[key][wrapper_field]`, if `map[key]` is a compound containing only
+/// that field.
+pub fn unwrap_compound(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    wrapper_field: impl AsRef<JavaStr>,
+) {
+    let Some(value) = map.get_mut(key.as_ref()) else {
+        return;
+    };
+    let JValue::Compound(wrapper) = &mut *value else {
+        return;
+    };
+    if wrapper.len() != 1 {
+        return;
+    }
+    if let Some(inner) = wrapper.remove(wrapper_field.as_ref()) {
+        *value = inner;
+    }
+}
+
+/// Replaces `map[key]` with a compound containing it as `wrapper_field`.
+pub fn wrap_in_compound(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    wrapper_field: impl Into<JavaString>,
+) {
+    let Some(value) = map.get_mut(key.as_ref()) else {
+        return;
+    };
+    let mut wrapper = JCompound::new();
+    wrapper.insert(wrapper_field, std::mem::replace(value, JValue::Byte(0)));
+    *value = JValue::Compound(wrapper);
+}
+
+/// Converts a compound with the keys `"0"`, `"1"`, ... into a list. Does nothing and logs a
+/// warning if the keys aren't consecutive integers starting at 0, or the values aren't all of the
+/// same type.
+pub fn compound_to_list(map: &mut JCompound, key: impl AsRef<JavaStr>) {
+    let key = key.as_ref();
+    let Some(value) = map.get_mut(key) else {
+        return;
+    };
+    let JValue::Compound(compound) = &mut *value else {
+        return;
+    };
+
+    let mut element_type = None;
+    for index in 0..compound.len() {
+        let Some(element) = compound.get(&index.to_string()[..]) else {
+            warn!("Could not convert {key} to a list: keys are not consecutive integers");
+            return;
+        };
+        let typ = nbt_type(element);
+        if element_type.is_some_and(|element_type| element_type != typ) {
+            warn!("Could not convert {key} to a list: values are not all of the same type");
+            return;
+        }
+        element_type = Some(typ);
+    }
+
+    let mut list = JList::new();
+    for index in 0..compound.len() {
+        if let Some(element) = compound.remove(&index.to_string()[..]) {
+            list.try_push(element);
+        }
+    }
+    *value = JValue::List(list);
+}
+
+/// Converts a list into a compound with the keys `"0"`, `"1"`, ...
+pub fn list_to_compound(map: &mut JCompound, key: impl AsRef<JavaStr>) {
+    let Some(value) = map.get_mut(key.as_ref()) else {
+        return;
+    };
+    let JValue::List(list) = &mut *value else {
+        return;
+    };
+    let mut compound = JCompound::with_capacity(list.len());
+    for (index, element) in list_into_values(std::mem::replace(list, JList::new()))
+        .into_iter()
+        .enumerate()
+    {
+        compound.insert(index.to_string(), element);
+    }
+    *value = JValue::Compound(compound);
+}
+
+/// Replaces each element of a list with the zero or more elements returned by `f`. Returns the
+/// number of resulting elements which were dropped because their type didn't match the rest of
+/// the list. A list which ends up empty becomes an `End` list.
+pub fn flat_map_list_in_map(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    f: impl Fn(JValue) -> Vec<JValue>,
+) -> usize {
+    let Some(JValue::List(list)) = map.get_mut(key.as_ref()) else {
+        return 0;
+    };
+    if list.is_empty() {
+        return 0;
+    }
+
+    let mut result = JList::new();
+    let mut dropped = 0;
+    let mut index = 0;
+    for element in list_into_values(std::mem::replace(list, JList::new())) {
+        for element in f(element) {
+            push_homogeneous(&mut result, element, index, &mut dropped);
+            index += 1;
+        }
+    }
+    *list = result;
+    dropped
+}
+
+/// Calls `f` with the slot and contents of each compound in the list at `key`, for lists such as
+/// inventories where the slot at `slot_key` rather than the list position identifies an entry.
+/// Entries may be sparse and in any order. Slots are read from any integer type. Bytes are read as
+/// unsigned, so a byte slot of -106 is passed as 150, while other types must be in `0..=255`.
+/// Elements without a valid slot are passed `missing_slot`, or skipped if it is `None`.
+pub fn convert_slotted_list_in_map(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    slot_key: impl AsRef<JavaStr>,
+    missing_slot: Option<u8>,
+    mut f: impl FnMut(u8, &mut JCompound),
+) {
+    let Some(JValue::List(valence_nbt::List::Compound(list))) = map.get_mut(key.as_ref()) else {
+        return;
+    };
+    let slot_key = slot_key.as_ref();
+    for element in list {
+        let slot = match element.get(slot_key) {
+            Some(JValue::Byte(slot)) => Some(*slot as u8),
+            slot => slot
+                .and_then(integer_value)
+                .and_then(|slot| u8::try_from(slot).ok()),
+        }
+        .or(missing_slot);
+        if let Some(slot) = slot {
+            f(slot, element);
+        }
+    }
+}
+
+/// Sorts the list at `key` by `cmp`. The sort is stable, so elements which compare equal keep
+/// their relative order.
+pub fn sort_list_in_map(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    cmp: impl Fn(&JValue, &JValue) -> Ordering,
+) {
+    let Some(JValue::List(list)) = map.get_mut(key.as_ref()) else {
+        return;
+    };
+    if list.is_empty() {
+        return;
+    }
+    let mut values = list_into_values(std::mem::replace(list, JList::new()));
+    values.sort_by(cmp);
+    for value in values {
+        list.try_push(value);
+    }
+}
+
+/// Removes each element of the list at `key` which `eq` considers equal to an earlier element,
+/// keeping the first occurrences in order. Returns the number of elements removed. `PartialEq`
+/// or [`semantically_eq`] can be passed as `eq`.
+pub fn dedup_list_in_map(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    eq: impl Fn(&JValue, &JValue) -> bool,
+) -> usize {
+    let Some(JValue::List(list)) = map.get_mut(key.as_ref()) else {
+        return 0;
+    };
+    if list.len() < 2 {
+        return 0;
+    }
+
+    let values = list_into_values(std::mem::replace(list, JList::new()));
+    let original_len = values.len();
+    let mut kept: Vec<JValue> = Vec::with_capacity(original_len);
+    for value in values {
+        if !kept.iter().any(|kept| eq(kept, &value)) {
+            kept.push(value);
+        }
+    }
+    let removed = original_len - kept.len();
+    for value in kept {
+        list.try_push(value);
+    }
+    removed
+}
+
+/// How entries are packed into a long array, as used by Minecraft for block states, biomes and
+/// heightmaps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitStorageLayout {
+    /// The number of bits per entry, from 1 to 32.
+    pub bits: u32,
+    /// Whether entries are padded so that they don't span two longs, which is the layout used
+    /// since 1.16. Before that, entries were packed tightly across long boundaries.
+    pub padded: bool,
+}
+
+impl BitStorageLayout {
+    pub fn padded(bits: u32) -> Self {
+        Self { bits, padded: true }
+    }
+
+    pub fn unpadded(bits: u32) -> Self {
+        Self {
+            bits,
+            padded: false,
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        (1..=32).contains(&self.bits)
+    }
+
+    fn entries_per_long(&self) -> usize {
+        (64 / self.bits) as usize
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    fn long_count(&self, entry_count: usize) -> usize {
+        if self.padded {
+            entry_count.div_ceil(self.entries_per_long())
+        } else {
+            (entry_count * self.bits as usize).div_ceil(64)
+        }
+    }
+
+    fn get(&self, longs: &[i64], index: usize) -> u64 {
+        if self.padded {
+            let long = longs[index / self.entries_per_long()] as u64;
+            let offset = (index % self.entries_per_long()) as u32 * self.bits;
+            (long >> offset) & self.mask()
+        } else {
+            let bit = index * self.bits as usize;
+            let offset = (bit % 64) as u32;
+            let mut value = longs[bit / 64] as u64 >> offset;
+            if offset + self.bits > 64 {
+                value |= (longs[bit / 64 + 1] as u64) << (64 - offset);
+            }
+            value & self.mask()
+        }
+    }
+
+    fn set(&self, longs: &mut [i64], index: usize, value: u64) {
+        if self.padded {
+            let offset = (index % self.entries_per_long()) as u32 * self.bits;
+            longs[index / self.entries_per_long()] |= (value << offset) as i64;
+        } else {
+            let bit = index * self.bits as usize;
+            let offset = (bit % 64) as u32;
+            longs[bit / 64] |= (value << offset) as i64;
+            if offset + self.bits > 64 {
+                longs[bit / 64 + 1] |= (value >> (64 - offset)) as i64;
+            }
+        }
+    }
+}
+
+/// Repacks the `entry_count` entries of the long array at `key` from one layout to another.
+/// Returns whether the array was repacked. It is left untouched, with a warning, if either layout
+/// is invalid, the array's length doesn't match `entry_count`, or an entry doesn't fit in the new
+/// number of bits.
+pub fn repack_long_array(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    from_bits: BitStorageLayout,
+    to_bits: BitStorageLayout,
+    entry_count: usize,
+) -> bool {
+    let key = key.as_ref();
+    let Some(JValue::LongArray(longs)) = map.get_mut(key) else {
+        return false;
+    };
+    if !from_bits.is_valid() || !to_bits.is_valid() {
+        warn!("Could not repack {key}: bits per entry must be between 1 and 32");
+        return false;
+    }
+    if longs.len() != from_bits.long_count(entry_count) {
+        warn!(
+            "Could not repack {key}: expected {} longs for {entry_count} entries, found {}",
+            from_bits.long_count(entry_count),
+            longs.len()
+        );
+        return false;
+    }
+
+    let mut result = vec![0; to_bits.long_count(entry_count)];
+    for index in 0..entry_count {
+        let value = from_bits.get(longs, index);
+        if value > to_bits.mask() {
+            warn!(
+                "Could not repack {key}: entry {index} ({value}) doesn't fit in {} bits",
+                to_bits.bits
+            );
+            return false;
+        }
+        to_bits.set(&mut result, index, value);
+    }
+    *longs = result;
+    true
+}
+
+fn list_into_values(list: JList) -> Vec<JValue> {
+    fn into_values<E: Into<JValue>>(list: Vec<E>) -> Vec<JValue> {
+        list.into_iter().map(Into::into).collect()
+    }
+
+    match list {
+        JList::End => Vec::new(),
+        JList::Byte(bytes) => into_values(bytes),
+        JList::Short(shorts) => into_values(shorts),
+        JList::Int(ints) => into_values(ints),
+        JList::Long(longs) => into_values(longs),
+        JList::Float(floats) => into_values(floats),
+        JList::Double(doubles) => into_values(doubles),
+        JList::ByteArray(byte_arrays) => into_values(byte_arrays),
+        JList::String(strings) => into_values(strings),
+        JList::List(lists) => into_values(lists),
+        JList::Compound(compounds) => into_values(compounds),
+        JList::IntArray(int_arrays) => into_values(int_arrays),
+        JList::LongArray(long_arrays) => into_values(long_arrays),
+    }
+}
+
+/// Replaces a string with the number it contains, ignoring surrounding whitespace and a trailing
+/// SNBT type suffix such as `1b` or `2.0f`. Strings which can't be parsed are left unchanged.
+pub fn string_to_number(map: &mut JCompound, key: impl AsRef<JavaStr>, kind: NumberKind) {
+    let Some(value) = map.get_mut(key.as_ref()) else {
+        return;
+    };
+    let JValue::String(str) = &*value else {
+        return;
+    };
+    match parse_number(str, kind) {
+        Some(number) => *value = number,
+        None => warn!("Could not parse {str:?} as {kind}"),
+    }
+}
+
+fn parse_number(str: &JavaStr, kind: NumberKind) -> Option<JValue> {
+    let str = str.as_str().ok()?.trim();
+    let str = match str.as_bytes().last()? {
+        b'b' | b'B' | b's' | b'S' | b'l' | b'L' | b'f' | b'F' | b'd' | b'D' => {
+            &str[..str.len() - 1]
+        }
+        _ => str,
+    };
+    match kind {
+        NumberKind::Byte => str.parse().ok().map(JValue::Byte),
+        NumberKind::Short => str.parse().ok().map(JValue::Short),
+        NumberKind::Int => str.parse().ok().map(JValue::Int),
+        NumberKind::Long => str.parse().ok().map(JValue::Long),
+        NumberKind::Float => str.parse().ok().map(JValue::Float),
+        NumberKind::Double => str.parse().ok().map(JValue::Double),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NarrowingPolicy {
+    /// Out of range values are clamped to the nearest value of the target type.
+    Clamp,
+    /// Out of range values are left as they are, in their original type.
+    LeaveUnchanged,
+}
+
+pub fn narrow_to_byte(map: &mut JCompound, key: impl AsRef<JavaStr>, policy: NarrowingPolicy) {
+    narrow_integer(
+        map,
+        key.as_ref(),
+        NumberKind::Byte,
+        policy,
+        i8::MIN.into(),
+        i8::MAX.into(),
+        |v| JValue::Byte(v as i8),
+    );
+}
+
+pub fn narrow_to_short(map: &mut JCompound, key: impl AsRef<JavaStr>, policy: NarrowingPolicy) {
+    narrow_integer(
+        map,
+        key.as_ref(),
+        NumberKind::Short,
+        policy,
+        i16::MIN.into(),
+        i16::MAX.into(),
+        |v| JValue::Short(v as i16),
+    );
+}
+
+pub fn narrow_to_int(map: &mut JCompound, key: impl AsRef<JavaStr>, policy: NarrowingPolicy) {
+    narrow_integer(
+        map,
+        key.as_ref(),
+        NumberKind::Int,
+        policy,
+        i32::MIN.into(),
+        i32::MAX.into(),
+        |v| JValue::Int(v as i32),
+    );
+}
+
+/// Converts every element of a numeric list to `target`. Integers which are out of range of an
+/// integer target are clamped with a warning, as with [`NarrowingPolicy::Clamp`], and floats are
+/// rounded towards zero and saturated, with NaN becoming 0. Lists of other types are left
+/// unchanged.
+pub fn coerce_list_numeric(map: &mut JCompound, key: impl AsRef<JavaStr>, target: NumberKind) {
+    let key = key.as_ref();
+    let Some(JValue::List(list)) = map.get_mut(key) else {
+        return;
+    };
+    if !matches!(
+        list,
+        JList::Byte(_)
+            | JList::Short(_)
+            | JList::Int(_)
+            | JList::Long(_)
+            | JList::Float(_)
+            | JList::Double(_)
+    ) || list.is_empty()
+        || list_element_type(list) == target.nbt_type()
+    {
+        return;
+    }
+
+    let mut clamped = 0;
+    let mut result = JList::new();
+    for value in list_into_values(std::mem::replace(list, JList::new())) {
+        let value = match integer_value(&value) {
+            Some(v) => match target {
+                NumberKind::Byte => JValue::Byte(clamp_integer(v, i8::MIN, i8::MAX, &mut clamped)),
+                NumberKind::Short => {
+                    JValue::Short(clamp_integer(v, i16::MIN, i16::MAX, &mut clamped))
+                }
+                NumberKind::Int => JValue::Int(clamp_integer(v, i32::MIN, i32::MAX, &mut clamped)),
+                NumberKind::Long => JValue::Long(v),
+                NumberKind::Float => JValue::Float(v as f32),
+                NumberKind::Double => JValue::Double(v as f64),
+            },
+            None => {
+                let v = match value {
+                    JValue::Float(v) => f64::from(v),
+                    JValue::Double(v) => v,
+                    _ => unreachable!("list is numeric"),
+                };
+                match target {
+                    NumberKind::Byte => JValue::Byte(v as i8),
+                    NumberKind::Short => JValue::Short(v as i16),
+                    NumberKind::Int => JValue::Int(v as i32),
+                    NumberKind::Long => JValue::Long(v as i64),
+                    NumberKind::Float => JValue::Float(v as f32),
+                    NumberKind::Double => JValue::Double(v),
+                }
+            }
+        };
+        result.try_push(value);
+    }
+    if clamped != 0 {
+        warn!("Clamped {clamped} values of {key} which were out of range for {target}");
+    }
+    *list = result;
+}
+
+fn clamp_integer<T: TryFrom<i64>>(value: i64, min: T, max: T, clamped: &mut usize) -> T {
+    T::try_from(value).unwrap_or_else(|_| {
+        *clamped += 1;
+        if value < 0 {
+            min
+        } else {
+            max
+        }
+    })
+}
+
+fn narrow_integer(
+    map: &mut JCompound,
+    key: &JavaStr,
+    kind: NumberKind,
+    policy: NarrowingPolicy,
+    min: i64,
+    max: i64,
+    to_value: impl FnOnce(i64) -> JValue,
+) {
+    let Some(value) = map.get_mut(key) else {
+        return;
+    };
+    let Some(original) = integer_value(value) else {
+        return;
+    };
+    let narrowed = if (min..=max).contains(&original) {
+        original
+    } else {
+        warn!("Value {original} of {key} is out of range for {kind}");
+        match policy {
+            NarrowingPolicy::Clamp => original.clamp(min, max),
+            NarrowingPolicy::LeaveUnchanged => return,
+        }
+    };
+    *value = to_value(narrowed);
+}
+
+/// Replaces the string at `key` with its entry in `table`, if it has one.
+pub fn remap_string_value(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    table: &HashMap<JavaString, JavaString>,
+) {
+    if let Some(JValue::String(value)) = map.get_mut(key.as_ref()) {
+        if let Some(replacement) = table.get(&value[..]) {
+            *value = replacement.clone();
+        }
+    }
+}
+
+/// Replaces each string in the list at `key` with its entry in `table`, if it has one.
+pub fn remap_string_values_in_list(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    table: &HashMap<JavaString, JavaString>,
+) {
+    if let Some(JValue::List(JList::String(values))) = map.get_mut(key.as_ref()) {
+        for value in values {
+            if let Some(replacement) = table.get(&value[..]) {
+                *value = replacement.clone();
+            }
+        }
+    }
+}
+
+/// Removes each flag in `flags` and sets its bit in a new int at `dest_key` if it was non-zero.
+/// Missing flags are treated as 0. Flags whose bit doesn't fit in an int, i.e. isn't less than 32,
+/// are left in place with a warning.
+pub fn pack_flags(map: &mut JCompound, flags: &[(&str, u32)], dest_key: impl Into<JavaString>) {
+    let mut packed = 0i32;
+    for &(key, bit) in flags {
+        let Some(mask) = 1i32.checked_shl(bit) else {
+            warn!("Cannot pack flag {key} into bit {bit}, which doesn't fit in an int");
+            continue;
+        };
+        if let Some(value) = map.remove(key) {
+            if integer_value(&value).is_some_and(|value| value != 0) {
+                packed |= mask;
+            }
+        }
+    }
+    map.insert(dest_key, JValue::Int(packed));
+}
+
+/// The reverse of [`pack_flags`]: removes the int at `src_key` and adds each flag in `flags` as a
+/// byte which is 1 if its bit was set, and 0 otherwise. Does nothing if `src_key` isn't an integer.
+/// Flags whose bit doesn't fit in an int are skipped with a warning.
+pub fn unpack_flags(map: &mut JCompound, src_key: impl AsRef<JavaStr>, flags: &[(&str, u32)]) {
+    let src_key = src_key.as_ref();
+    let Some(packed) = map.get(src_key).and_then(integer_value) else {
+        return;
+    };
+    map.remove(src_key);
+    for &(key, bit) in flags {
+        let Some(mask) = 1i32.checked_shl(bit) else {
+            warn!("Cannot unpack flag {key} from bit {bit}, which doesn't fit in an int");
+            continue;
+        };
+        map.insert(key, JValue::Byte((packed as i32 & mask != 0).into()));
+    }
+}
+
+/// Removes the fields in `sources` and puts their values at `dest_key`, either in a compound keyed
+/// by source name, or in a list in the order of `sources`, e.g. to combine `x`, `y` and `z` into a
+/// position. Missing sources are skipped, unless `require_all` is set, in which case nothing is
+/// changed. Nothing is changed either if no source exists, or if the values can't be put in one
+/// list because their types differ. Returns whether the fields were combined.
+pub fn combine_fields(
+    map: &mut JCompound,
+    sources: &[&str],
+    dest_key: impl Into<JavaString>,
+    as_list: bool,
+    require_all: bool,
+) -> bool {
+    let present: Vec<&str> = sources
+        .iter()
+        .copied()
+        .filter(|&key| map.contains_key(key))
+        .collect();
+    if present.is_empty() || (require_all && present.len() != sources.len()) {
+        return false;
+    }
+    if as_list {
+        let mut types = present.iter().filter_map(|&key| map.get(key).map(nbt_type));
+        let first_type = types.next();
+        if types.any(|typ| Some(typ) != first_type) {
+            return false;
+        }
+    }
+
+    let combined = if as_list {
+        let mut list = JList::new();
+        for key in present {
+            // the types were checked above
+            list.try_push(map.remove(key).unwrap());
+        }
+        JValue::List(list)
+    } else {
+        let mut compound = JCompound::with_capacity(present.len());
+        for key in present {
+            compound.insert(key, map.remove(key).unwrap());
+        }
+        JValue::Compound(compound)
+    };
+    map.insert(dest_key, combined);
+    true
+}
+
+/// The reverse of [`combine_fields`] for lists: removes the list at `key` and puts each element at
+/// the corresponding key in `dest_keys`, e.g. to split `Pos` into `PosX`, `PosY` and `PosZ`. Logs
+/// a warning and does nothing if the list's length doesn't match. Returns whether the list was
+/// split.
+pub fn split_list_to_fields(
+    map: &mut JCompound,
+    key: impl AsRef<JavaStr>,
+    dest_keys: &[&str],
+) -> bool {
+    let key = key.as_ref();
+    let Some(JValue::List(list)) = map.get(key) else {
+        return false;
+    };
+    if list.len() != dest_keys.len() {
+        warn!(
+            "Cannot split list {key} of length {} into {} fields",
+            list.len(),
+            dest_keys.len()
+        );
+        return false;
+    }
+    let Some(JValue::List(list)) = map.remove(key) else {
+        unreachable!();
+    };
+    for (&dest_key, value) in dest_keys.iter().zip(list_into_values(list)) {
+        map.insert(dest_key, value);
+    }
+    true
+}
+
+fn integer_value(value: &JValue) -> Option<i64> {
+    match *value {
+        JValue::Byte(v) => Some(v.into()),
+        JValue::Short(v) => Some(v.into()),
+        JValue::Int(v) => Some(v.into()),
+        JValue::Long(v) => Some(v),
+        _ => None,
+    }
+}
+
+/// Replaces the pre-flattening numeric block id at `id_key` with the string id looked up in
+/// `table` by `(id, meta)`, and removes `meta_key`. The metadata is masked to its low 4 bits and
+/// treated as 0 if missing; if `(id, meta)` isn't in the table, `(id, 0)` is tried instead. Byte
+/// ids are read as unsigned. Returns whether the id was replaced; unknown ids are left untouched.
+pub fn legacy_block_to_string(
+    map: &mut JCompound,
+    id_key: impl AsRef<JavaStr>,
+    meta_key: impl AsRef<JavaStr>,
+    table: &HashMap<(u16, u8), JavaString>,
+) -> bool {
+    let id_key = id_key.as_ref();
+    let meta_key = meta_key.as_ref();
+    let id = match map.get(id_key) {
+        Some(&JValue::Byte(id)) => i64::from(id as u8),
+        Some(value) => match integer_value(value) {
+            Some(id) => id,
+            None => return false,
+        },
+        None => return false,
+    };
+    let Ok(id) = u16::try_from(id) else {
+        return false;
+    };
+    let meta = map
+        .get(meta_key)
+        .and_then(integer_value)
+        .map_or(0, |meta| (meta & 15) as u8);
+
+    let Some(new_id) = table.get(&(id, meta)).or_else(|| table.get(&(id, 0))) else {
+        return false;
+    };
+    map.insert(id_key, new_id.clone());
+    map.remove(meta_key);
+    true
+}
+
+/// Runs `then` if the integer at `version_key` is below `threshold` or missing, and then sets it
+/// to `threshold`. Returns whether `then` was run.
+pub fn convert_if_field_below(
+    map: &mut JCompound,
+    version_key: impl AsRef<JavaStr>,
+    threshold: i32,
+    then: impl FnOnce(&mut JCompound),
+) -> bool {
+    let version_key = version_key.as_ref();
+    if let Some(version) = map.get(version_key).and_then(integer_value) {
+        if version >= i64::from(threshold) {
+            return false;
+        }
+    }
+    then(map);
+    map.insert(version_key.to_owned(), JValue::Int(threshold));
+    true
+}
+
+/// Compares two values structurally, treating numbers as equal if they have the same numeric value
+/// regardless of their tag type (e.g. `1b`, `1` and `1.0d`), and likewise for the elements of
+/// byte, int and long arrays. Compounds and lists are compared recursively.
+pub fn semantically_eq(a: &JValue, b: &JValue) -> bool {
+    semantically_eq_ref(a.as_value_ref(), b.as_value_ref())
+}
+
+fn semantically_eq_ref(a: JValueRef, b: JValueRef) -> bool {
+    match (a, b) {
+        (JValueRef::String(a), JValueRef::String(b)) => a == b,
+        (JValueRef::Compound(a), JValueRef::Compound(b)) => {
+            a.len() == b.len()
+                && a.iter().all(|(key, a)| {
+                    b.get(&key[..])
+                        .is_some_and(|b| semantically_eq_ref(a.as_value_ref(), b.as_value_ref()))
+                })
+        }
+        (JValueRef::List(a), JValueRef::List(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b.iter())
+                    .all(|(a, b)| semantically_eq_ref(a, b))
+        }
+        (a, b) => match (numeric_value(&a), numeric_value(&b)) {
+            (Some(NumericValue::Integer(a)), Some(NumericValue::Integer(b))) => a == b,
+            (Some(a), Some(b)) => a.as_f64() == b.as_f64(),
+            _ => match (array_values(&a), array_values(&b)) {
+                (Some(a), Some(b)) => a.eq(b),
+                _ => false,
+            },
+        },
+    }
+}
+
+enum NumericValue {
+    Integer(i64),
+    Float(f64),
+}
+
+impl NumericValue {
+    fn as_f64(&self) -> f64 {
+        match *self {
+            NumericValue::Integer(v) => v as f64,
+            NumericValue::Float(v) => v,
+        }
+    }
+}
+
+fn numeric_value(value: &JValueRef) -> Option<NumericValue> {
+    match *value {
+        JValueRef::Byte(v) => Some(NumericValue::Integer((*v).into())),
+        JValueRef::Short(v) => Some(NumericValue::Integer((*v).into())),
+        JValueRef::Int(v) => Some(NumericValue::Integer((*v).into())),
+        JValueRef::Long(v) => Some(NumericValue::Integer(*v)),
+        JValueRef::Float(v) => Some(NumericValue::Float((*v).into())),
+        JValueRef::Double(v) => Some(NumericValue::Float(*v)),
+        _ => None,
+    }
+}
+
+fn array_values<'a>(value: &JValueRef<'a>) -> Option<Box<dyn Iterator<Item = i64> + 'a>> {
+    match *value {
+        JValueRef::ByteArray(v) => Some(Box::new(v.iter().map(|&v| v.into()))),
+        JValueRef::IntArray(v) => Some(Box::new(v.iter().map(|&v| v.into()))),
+        JValueRef::LongArray(v) => Some(Box::new(v.iter().copied())),
+        _ => None,
+    }
+}
+
+pub fn swap_int_array_endianness(map: &mut JCompound, key: impl AsRef<JavaStr>) {
+    if let Some(JValue::IntArray(array)) = map.get_mut(key.as_ref()) {
+        for value in array {
+            *value = value.swap_bytes();
+        }
+    }
+}
+
+pub fn swap_long_array_endianness(map: &mut JCompound, key: impl AsRef<JavaStr>) {
+    if let Some(JValue::LongArray(array)) = map.get_mut(key.as_ref()) {
+        for value in array {
+            *value = value.swap_bytes();
+        }
+    }
+}
+
+/// Shrinks the storage of `map` to fit its entries, e.g. after a conversion removed many keys.
+/// Nested values are left alone, see [`compact`].
+pub fn shrink_compound(map: &mut JCompound) {
+    map.shrink_to_fit();
+}
+
+/// Shrinks the storage of `map`, and if `recursive` is set, of every compound, list, array and
+/// string nested in it.
+pub fn compact(map: &mut JCompound, recursive: bool) {
+    map.shrink_to_fit();
+    if recursive {
+        for value in map.values_mut() {
+            compact_value(value);
+        }
+    }
+}
+
+fn compact_value(value: &mut JValue) {
+    match value {
+        JValue::ByteArray(array) => array.shrink_to_fit(),
+        JValue::String(str) => str.shrink_to_fit(),
+        JValue::List(list) => compact_list(list),
+        JValue::Compound(compound) => compact(compound, true),
+        JValue::IntArray(array) => array.shrink_to_fit(),
+        JValue::LongArray(array) => array.shrink_to_fit(),
+        _ => {}
+    }
+}
+
+fn compact_list(list: &mut JList) {
+    match list {
+        JList::End => {}
+        JList::Byte(bytes) => bytes.shrink_to_fit(),
+        JList::Short(shorts) => shorts.shrink_to_fit(),
+        JList::Int(ints) => ints.shrink_to_fit(),
+        JList::Long(longs) => longs.shrink_to_fit(),
+        JList::Float(floats) => floats.shrink_to_fit(),
+        JList::Double(doubles) => doubles.shrink_to_fit(),
+        JList::ByteArray(byte_arrays) => {
+            byte_arrays.shrink_to_fit();
+            byte_arrays.iter_mut().for_each(Vec::shrink_to_fit);
+        }
+        JList::String(strings) => {
+            strings.shrink_to_fit();
+            strings.iter_mut().for_each(JavaString::shrink_to_fit);
+        }
+        JList::List(lists) => {
+            lists.shrink_to_fit();
+            lists.iter_mut().for_each(compact_list);
+        }
+        JList::Compound(compounds) => {
+            compounds.shrink_to_fit();
+            for compound in compounds {
+                compact(compound, true);
+            }
+        }
+        JList::IntArray(int_arrays) => {
+            int_arrays.shrink_to_fit();
+            int_arrays.iter_mut().for_each(Vec::shrink_to_fit);
+        }
+        JList::LongArray(long_arrays) => {
+            long_arrays.shrink_to_fit();
+            long_arrays.iter_mut().for_each(Vec::shrink_to_fit);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct ResourceLocation<'a> {
+    pub namespace: &'a JavaStr,
+    pub path: &'a JavaStr,
+}
+
+impl<'a> ResourceLocation<'a> {
+    pub const DEFAULT_NAMESPACE: &'static str = "minecraft";
+
+    /// Parses a resource location the same way vanilla does: a missing or empty namespace
+    /// defaults to `minecraft`, and `None` is returned if either part contains invalid characters.
+    pub fn parse(id: &'a JavaStr) -> Option<Self> {
+        let default_namespace = JavaStr::from_str(Self::DEFAULT_NAMESPACE);
+        let (namespace, path) = match id.split_once(':') {
+            Some((namespace, path)) if !namespace.is_empty() => (namespace, path),
+            Some((_, path)) => (default_namespace, path),
+            None => (default_namespace, id),
+        };
+
+        let valid_namespace = namespace
+            .as_bytes()
+            .iter()
+            .all(|&b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' | b'.'));
+        let valid_path = path
+            .as_bytes()
+            .iter()
+            .all(|&b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' | b'.' | b'/'));
+        if !valid_namespace || !valid_path {
+            return None;
+        }
+
+        Some(Self { namespace, path })
+    }
+
+    pub fn to_java_string(&self) -> JavaString {
+        let mut result = JavaString::with_capacity(self.namespace.len() + 1 + self.path.len());
+        result.push_java_str(self.namespace);
+        result.push(':');
+        result.push_java_str(self.path);
+        result
+    }
+}
+
+pub fn namespace_ids_in_map(map: &mut JCompound, keys: &[&str]) {
+    for key in keys {
+        if let Some(JValue::String(id)) = map.get_mut(*key) {
+            let namespaced = ResourceLocation::parse(&id[..]).map(|id| id.to_java_string());
+            if let Some(namespaced) = namespaced {
+                *id = namespaced;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "snbt")]
+pub fn compound_from_snbt(snbt: &str) -> crate::Result<JCompound> {
+    match valence_nbt::snbt::from_snbt_str(snbt) {
+        Ok(valence_nbt::Value::Compound(compound)) => Ok(crate::compound_to_java(compound)),
+        Ok(value) => Err(crate::ConversionError {
+            message: format!(
+                "Expected snbt compound, got {}",
+                nbt_type(&crate::value_to_java(value))
+            ),
+        }),
+        Err(err) => Err(crate::ConversionError {
+            message: format!("Invalid snbt: {err}"),
+        }),
+    }
+}
+
+/// Fails if any string in the compound contains unpaired surrogates, which snbt can't represent.
+#[cfg(feature = "snbt")]
+pub fn compound_to_snbt(compound: &JCompound) -> crate::Result<String> {
+    let compound = crate::compound_from_java(compound.clone())?;
+    Ok(valence_nbt::snbt::to_snbt_string(
+        &valence_nbt::Value::Compound(compound),
+    ))
 }